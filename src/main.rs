@@ -4,46 +4,102 @@ pub mod row4;
 extern crate lazy_static;
 extern crate rand;
 
+use std::env;
 use std::io;
+use std::process;
 
 use row4::*;
-use row4::board::Board;
-use row4::cache::BoardCache;
+use row4::board::{Board, GameStatus, MoveError};
+use row4::engine::{self, Engine, SearchLimits};
 
 fn main() {
-    play_game();
+    // usage: row4 [--engine <name>] [moves]
+    // optionally start from a position given in move notation, e.g. "4453"
+    let mut engine = engine::engine_by_name("minmax").unwrap();
+    let mut start = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--engine" {
+            engine = args.next().and_then(|name| engine::engine_by_name(&name)).unwrap_or_else(|| {
+                println!("unknown engine, expected one of {:?}", engine::ENGINE_NAMES);
+                process::exit(1);
+            });
+        } else {
+            start = Some(arg);
+        }
+    }
+
+    let board = match start {
+        None => Board::new(),
+        Some(moves) => Board::from_moves_str(&moves).unwrap_or_else(|err| {
+            println!("invalid start position: {}", err);
+            process::exit(1);
+        })
+    };
+    play_game(board, &mut *engine);
 }
 
-fn play_game() {
-    let mut board = Board::new();
-    let mut cache = BoardCache::new();
-    let ai_color = Color::Red;
+fn play_game(mut board: Board, engine: &mut dyn Engine) {
+    let ai_color = board.color_to_move;
 
-    while board.winner.is_none() {
+    while board.status == GameStatus::Ongoing {
         // AI move
         {
-            let (variant, eval, num_moves, num_positions) =
-                row4::minmax::iterative_minmax(&board, ai_color, 5_000, &mut cache, monte_carlo::evaluate_in_parallel);
-
-            board.play_move(*variant.last().unwrap(), true);
-            let mut print_variant = variant.clone();
-            print_variant.reverse();
-            print_variant = print_variant.iter().map(|&c| c + 1 ).collect();
+            let result = match engine.choose_move(&board, SearchLimits::default()) {
+                Some(result) => result,
+                None => {
+                    println!("the {} engine can't play on this board", engine.name());
+                    break;
+                }
+            };
+            let eval = result.eval.relative_to(board.num_pieces());
+            board.play_move(result.best);
+            let print_variant: Vec<_> = result.variant.iter().map(|&c| c + 1).collect();
 
-            println!("ai moves: {:?}, win rate: {} (moves: {}, positions: {})\n{}\n", print_variant, eval, num_moves, num_positions, board);
+            println!("ai moves: {:?}, {} ({}: {} positions)\n{}\n", print_variant, eval, engine.name(), result.nodes, board);
         }
 
-        if board.winner.is_some() {
+        if board.status != GameStatus::Ongoing {
             break;
         }
 
-        // player move
-        println!("Your move: ");
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let column = input.trim().parse::<Column>().unwrap() - 1;
+        // keep thinking while waiting for the player
+        engine.ponder(&board);
+
+        // player move, possibly after taking back the previous moves
+        let column = loop {
+            println!("Your move (or 'u' to take back your last move): ");
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).unwrap();
+            if input.trim() == "u" {
+                if board.played_moves().len() > 2 {
+                    board.undo_moves(2);
+                    println!("took back the last two moves\n{}\n", board);
+                } else {
+                    println!("nothing to take back");
+                }
+                continue;
+            }
+            let result = match input.trim().parse::<Column>() {
+                Ok(column) if column > 0 => board.try_play_move(column - 1).map(|_| column),
+                _ => Err(MoveError::ColumnOutOfRange)
+            };
+            match result {
+                Ok(column) => break column,
+                Err(err) => println!("illegal move: {}", err)
+            }
+        };
 
-        board.play_move(column, true);
         println!("player move: {}\n{}\n", column, board);
     }
+
+    match board.status {
+        GameStatus::Won(color) => {
+            let line: Vec<_> = board.winning_line().unwrap().iter().map(|&(column, row)| (column + 1, row + 1)).collect();
+            let winner = if color == ai_color { "ai" } else { "you" };
+            println!("{} won with (column, row): {:?}", winner, line);
+        },
+        GameStatus::Draw => println!("draw"),
+        GameStatus::Ongoing => ()
+    }
 }
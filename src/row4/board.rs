@@ -1,119 +1,292 @@
+use std::error::Error;
 use std::fmt;
 
 use row4::*;
+use row4::config::BoardConfig;
 use row4::move_list::MoveList;
 
+use rand::{Rng, SeedableRng, XorShiftRng};
+
 lazy_static! {
-    static ref WIN_MASKS: Vec<u64> = Board::compute_win_masks();
+    // fixed seed, so that hashes stay the same between runs
+    static ref ZOBRIST_KEYS: [[u64; 64]; 2] = Board::compute_zobrist_keys(XorShiftRng::from_seed([0x726f_7734, 0x5a6f_6272, 0x6973_7400, 0x1234_5678]));
+}
+
+/// bit distances between neighbouring positions on the standard board:
+/// horizontal, both diagonals and vertical
+const ROW4_SHIFTS: [u32; 4] = [1, 7, 9, 8];
+
+/// The state of a game on a board.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GameStatus {
+    Ongoing,
+    Won(Color),
+    Draw,
 }
 
+/// Reasons why a move can't be played.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MoveError {
+    ColumnOutOfRange,
+    ColumnFull,
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MoveError::ColumnOutOfRange => write!(dest, "the column is not on the board"),
+            MoveError::ColumnFull => write!(dest, "the column is already full"),
+            MoveError::GameOver => write!(dest, "the game is already over"),
+        }
+    }
+}
+
+impl Error for MoveError {}
+
 /// A simple row4 board. Each color is represented as a bit array.
 ///
 /// Bits 0..6 of the first byte are used as the lowest row of the board,
 /// bits 0..6 of the second byte as the second-lowest row, and so on.
+/// Other board sizes use the layout described in `BoardConfig`.
+///
+/// The board also keeps a 64 bit Zobrist hash of its position and of its
+/// left/right mirror image, which are updated with every move played or taken back.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Board {
     pub red: u64,
     pub blue: u64,
-    pub column_heights: [Column; 7],
+    pub column_heights: [Column; MAX_WIDTH],
     pub color_to_move: Color,
     pub moves: MoveList,
-    pub winner: Option<Color>,
+    pub status: GameStatus,
+    pub config: BoardConfig,
+    history: [Column; MAX_CELLS],
+    num_played: u8,
+    hash: u64,
+    mirrored_hash: u64,
 }
 
 impl Board {
-    /// creates an empty board
+    /// creates an empty standard board
     pub fn new() -> Board {
+        Board::with_config(BoardConfig::standard())
+    }
+
+    /// creates an empty board with the given dimensions and rules
+    pub fn with_config(config: BoardConfig) -> Board {
         Board {
             red: 0,
             blue: 0,
-            column_heights: [0; 7],
+            column_heights: [0; MAX_WIDTH],
             color_to_move: Color::Red,
-            moves: MoveList::full(),
-            winner: None
+            moves: MoveList::full(config),
+            status: GameStatus::Ongoing,
+            config,
+            history: [0; MAX_CELLS],
+            num_played: 0,
+            hash: 0,
+            mirrored_hash: 0,
         }
     }
 
-    /// utility to complete a bit mask from a given position
-    fn position_mask(column: Column, height: u8) -> u64 {
-        1u64 << (height * 8 + column)
+    /// creates a board from the bit arrays of both colors
+    ///
+    /// The pieces are not validated, so they have to rest on each other. Red is assumed
+    /// to have started, so blue is to move if red has more pieces on the board.
+    /// As the order of the moves is unknown, the board has no move history.
+    pub fn from_bits(config: BoardConfig, red: u64, blue: u64) -> Board {
+        let mut board = Board::with_config(config);
+        board.red = red;
+        board.blue = blue;
+        for column in 0..config.width() {
+            let pieces = (0..config.height()).filter(|&row| (red | blue) & config.position_mask(column, row) != 0).count();
+            board.column_heights[column as usize] = pieces as u8;
+        }
+        if red.count_ones() > blue.count_ones() {
+            board.color_to_move = Color::Blue;
+        }
+        board.moves = board.compute_moves();
+        board.status = board.compute_status();
+        board.hash = Board::compute_hash(red, blue);
+        board.mirrored_hash = Board::compute_hash(config.mirror_bits(red), config.mirror_bits(blue));
+        board
     }
 
-    fn compute_win_masks() -> Vec<u64> {
-        let mut masks = Vec::new();
-        // vertical row4s
-        for col in 0..7u8 {
-            for row in 0..3u8 {
-                let mut mask = 0u64;
-                for idx in 0..4u8 {
-                    mask |= Board::position_mask(col, row + idx);
-                }
-                masks.push(mask);
+    fn compute_zobrist_keys<R: Rng>(mut rng: R) -> [[u64; 64]; 2] {
+        let mut keys = [[0u64; 64]; 2];
+        for color_keys in keys.iter_mut() {
+            for key in color_keys.iter_mut() {
+                *key = rng.next_u64();
             }
         }
-        // horizontal row4s
-        for row in 0..6u8 {
-            for col in 0..4u8 {
-                let mut mask = 0u64;
-                for idx in 0..4u8 {
-                    mask |= Board::position_mask(col + idx, row);
-                }
-                masks.push(mask);
+        keys
+    }
+
+    /// returns the Zobrist key for a piece of the given color at the given position
+    fn zobrist_key(color: Color, mask: u64) -> u64 {
+        ZOBRIST_KEYS[color as usize][mask.trailing_zeros() as usize]
+    }
+
+    /// computes the Zobrist hash for the given bit arrays from scratch
+    pub fn compute_hash(red: u64, blue: u64) -> u64 {
+        let mut hash = 0;
+        for &(color, bits) in &[(Color::Red, red), (Color::Blue, blue)] {
+            let mut remaining = bits;
+            while remaining != 0 {
+                let mask = remaining & remaining.wrapping_neg();
+                hash ^= Board::zobrist_key(color, mask);
+                remaining ^= mask;
             }
         }
-        // diagonal row4s
-        for row in 0..3u8 {
-            for col in 0..4u8 {
-                let mut mask_left = 0u64;
-                let mut mask_right = 0u64;
-                for idx in 0..4u8 {
-                    mask_left |= Board::position_mask(col + idx, row + idx);
-                    mask_right |= Board::position_mask(col + 3 - idx, row + idx);
-                }
-                masks.push(mask_left);
-                masks.push(mask_right);
-            }
+        hash
+    }
+
+    /// returns the Zobrist hash of the current position
+    ///
+    /// The side to move is not part of the hash, as it follows from the position itself.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// returns a key that is shared by this position and its left/right mirror image
+    ///
+    /// Of the two Zobrist hashes, the smaller one is used as the key.
+    pub fn canonical_key(&self) -> u64 {
+        u64::min(self.hash, self.mirrored_hash)
+    }
+
+    /// checks if the canonical key represents the mirror image of this position
+    pub fn is_canonical_mirrored(&self) -> bool {
+        self.mirrored_hash < self.hash
+    }
+
+    /// maps a column between this board and the orientation of its canonical key
+    ///
+    /// Mirroring twice is the identity, so this works in both directions.
+    pub fn canonical_column(&self, column: Column) -> Column {
+        if self.is_canonical_mirrored() {
+            self.config.mirror_column(column)
+        } else {
+            column
         }
-        masks
     }
 
     /// play a series of moves
     pub fn play_moves(&mut self, moves: &[Column]) {
         for &column in moves {
-            self.play_move(column, true);
+            self.play_move(column);
+        }
+    }
+
+    /// play a move in the specified column, if it is legal
+    pub fn try_play_move(&mut self, column: Column) -> Result<(), MoveError> {
+        if column >= self.config.width() {
+            return Err(MoveError::ColumnOutOfRange);
+        }
+        if self.status != GameStatus::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+        if self.height(column) == self.config.height() {
+            return Err(MoveError::ColumnFull);
         }
+        self.play_move(column);
+        Ok(())
     }
 
     /// play a move in the specified column
-    /// does not check if the column is legal - this has to be done beforehand!
-    pub fn play_move(&mut self, column: Column, gen_next_moves: bool) {
+    /// does not check if the column is legal - this has to be done beforehand, or use `try_play_move`!
+    pub fn play_move(&mut self, column: Column) {
         let height = self.height(column);
-        let mask = Board::position_mask(column, height);
+        let mask = self.config.position_mask(column, height);
 
         self.column_heights[column as usize] = height + 1;
+        if height + 1 == self.config.height() {
+            self.moves = self.compute_moves();
+        }
 
-        match self.color_to_move {
-            Color::Red => self.red = self.red | mask,
-            Color::Blue => self.blue = self.blue | mask
+        let bits = match self.color_to_move {
+            Color::Red => { self.red |= mask; self.red },
+            Color::Blue => { self.blue |= mask; self.blue }
         };
+        self.hash ^= Board::zobrist_key(self.color_to_move, mask);
+        self.mirrored_hash ^= Board::zobrist_key(self.color_to_move, self.config.position_mask(self.config.mirror_column(column), height));
+        if self.is_line_at(bits, column, height) {
+            self.status = GameStatus::Won(self.color_to_move);
+        } else if self.moves.is_empty() && self.status == GameStatus::Ongoing {
+            self.status = GameStatus::Draw;
+        }
+
+        self.history[self.num_played as usize] = column;
+        self.num_played += 1;
+
+        self.color_to_move = self.color_to_move.switch();
+    }
+
+    /// checks if playing the specified column would win the game for the given color
+    pub fn is_winning_move(&self, column: Column, color: Color) -> bool {
+        let height = self.height(column);
+        let bits = match color {
+            Color::Red => self.red,
+            Color::Blue => self.blue
+        };
+        self.is_line_at(bits | self.config.position_mask(column, height), column, height)
+    }
+
+    /// take back the last move played on this board, returning its column
+    /// returns None if there is no move left to take back
+    pub fn undo_move(&mut self) -> Option<Column> {
+        if self.num_played == 0 {
+            return None;
+        }
+        self.num_played -= 1;
+        let column = self.history[self.num_played as usize];
+        self.history[self.num_played as usize] = 0;
+        let height = self.height(column) - 1;
+        let mask = self.config.position_mask(column, height);
 
-        if gen_next_moves {
-            self.moves = MoveList::from(&self.compute_moves());
+        self.column_heights[column as usize] = height;
+        if height + 1 == self.config.height() {
+            self.moves = self.compute_moves();
         }
         self.color_to_move = self.color_to_move.switch();
-        self.winner = self.compute_winner();
+
+        match self.color_to_move {
+            Color::Red => self.red &= !mask,
+            Color::Blue => self.blue &= !mask
+        };
+        self.hash ^= Board::zobrist_key(self.color_to_move, mask);
+        self.mirrored_hash ^= Board::zobrist_key(self.color_to_move, self.config.position_mask(self.config.mirror_column(column), height));
+
+        self.status = self.compute_status();
+        Some(column)
+    }
+
+    /// take back the last `count` moves, returning how many moves were actually taken back
+    pub fn undo_moves(&mut self, count: usize) -> usize {
+        let mut undone = 0;
+        while undone < count && self.undo_move().is_some() {
+            undone += 1;
+        }
+        undone
+    }
+
+    /// returns all moves played on this board so far, in the order they were played
+    pub fn played_moves(&self) -> &[Column] {
+        &self.history[..self.num_played as usize]
     }
 
     /// computes all currently available moves for this board
-    fn compute_moves(&self) -> Vec<Column> {
-        let mut moves = Vec::new();
-        for i in 0..7usize {
-            let column = COLUMNS[i];
-            if self.height(column) < 6 {
-                moves.push(column);
+    fn compute_moves(&self) -> MoveList {
+        let mut moves = [0; MAX_WIDTH];
+        let mut len = 0;
+        for &column in self.config.columns() {
+            if self.height(column) < self.config.height() {
+                moves[len] = column;
+                len += 1;
             }
         }
-        moves
+        MoveList::from(&moves[..len])
     }
 
     /// returns the height of the specified column
@@ -121,37 +294,125 @@ impl Board {
         self.column_heights[column as usize]
     }
 
-    /// checks if one of the players has won
-    fn compute_winner(&self) -> Option<Color> { // TODO this has lots of optimization potential
-        for &mask in WIN_MASKS.iter() {
-            if self.red & mask == mask {
-                return Some(Color::Red);
+    /// returns the number of pieces on the board
+    pub fn num_pieces(&self) -> u8 {
+        (self.red | self.blue).count_ones() as u8
+    }
+
+    /// returns the color that has won the game, if any
+    pub fn winner(&self) -> Option<Color> {
+        match self.status {
+            GameStatus::Won(color) => Some(color),
+            _ => None
+        }
+    }
+
+    /// returns the (column, row) positions of the winning line, if the game has been won
+    ///
+    /// If the line is longer than needed, all of its positions are returned.
+    pub fn winning_line(&self) -> Option<Vec<(Column, u8)>> {
+        match self.status {
+            GameStatus::Won(Color::Red) => self.config.find_line(self.red),
+            GameStatus::Won(Color::Blue) => self.config.find_line(self.blue),
+            _ => None
+        }
+    }
+
+    /// determines the status of the game, looking at the whole board
+    fn compute_status(&self) -> GameStatus {
+        match self.compute_winner() {
+            Some(color) => GameStatus::Won(color),
+            None if self.moves.is_empty() => GameStatus::Draw,
+            None => GameStatus::Ongoing
+        }
+    }
+
+    /// checks if one of the players has won, looking at the whole board
+    fn compute_winner(&self) -> Option<Color> {
+        if self.has_line(self.red) {
+            Some(Color::Red)
+        } else if self.has_line(self.blue) {
+            Some(Color::Blue)
+        } else {
+            None
+        }
+    }
+
+    /// checks if the bits contain a winning line through the given position (which has to be part of the bits)
+    pub fn is_line_at(&self, bits: u64, column: Column, height: u8) -> bool {
+        if self.config.is_standard() {
+            Board::is_row4_at(bits, self.config.position_mask(column, height))
+        } else {
+            self.config.is_line_at(bits, column, height)
+        }
+    }
+
+    /// checks if the bits contain a winning line anywhere
+    pub fn has_line(&self, bits: u64) -> bool {
+        if self.config.is_standard() {
+            Board::has_row4(bits)
+        } else {
+            self.config.has_line(bits)
+        }
+    }
+
+    /// checks if the bits contain four in a row anywhere on the standard board
+    fn has_row4(bits: u64) -> bool {
+        for &shift in ROW4_SHIFTS.iter() {
+            let pairs = bits & (bits >> shift);
+            if pairs & (pairs >> (2 * shift)) != 0 {
+                return true;
             }
-            if self.blue & mask == mask {
-                return Some(Color::Blue);
+        }
+        false
+    }
+
+    /// checks if the bits contain four in a row through the single position given by mask
+    /// (which has to be part of the bits) on the standard board
+    ///
+    /// Only the lines through that position are looked at, which is all that can change
+    /// when a piece is placed there: a line starts at most three steps before it. The empty
+    /// 8th bit of each row stops horizontal and diagonal lines from wrapping around into the
+    /// next row.
+    fn is_row4_at(bits: u64, mask: u64) -> bool {
+        for &shift in ROW4_SHIFTS.iter() {
+            let pairs = bits & (bits >> shift);
+            let starts = mask | mask >> shift | mask >> (2 * shift) | mask >> (3 * shift);
+            if pairs & (pairs >> (2 * shift)) & starts != 0 {
+                return true;
             }
         }
-        None
+        false
     }
 
     /// reset the board to an empty one
     pub fn reset(&mut self) {
         self.red = 0;
         self.blue = 0;
-        self.column_heights = [0; 7];
+        self.column_heights = [0; MAX_WIDTH];
         self.color_to_move = Color::Red;
-        self.moves = MoveList::full();
-        self.winner = None;
+        self.moves = MoveList::full(self.config);
+        self.status = GameStatus::Ongoing;
+        self.history = [0; MAX_CELLS];
+        self.num_played = 0;
+        self.hash = 0;
+        self.mirrored_hash = 0;
+    }
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
     }
 }
 
 impl fmt::Display for Board {
     fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
         let mut board = Vec::<String>::new();
-        for row in 0..6u8 {
+        for row in (0..self.config.height()).rev() {
             let mut row_string = String::new();
-            for column in 0..7u8 {
-                let mask = Board::position_mask(column, 5 - row);
+            for column in 0..self.config.width() {
+                let mask = self.config.position_mask(column, row);
                 if self.red & mask != 0 {
                     row_string.push('x');
                 } else if self.blue & mask != 0 {
@@ -167,15 +428,9 @@ impl fmt::Display for Board {
     }
 }
 
-#[test]
-fn test_position_mask() {
-    assert_eq!(Board::position_mask(3, 1), 2048);
-    assert_eq!(Board::position_mask(6, 5), 1 << (8 * 5 + 6));
-}
-
 #[test]
 fn test_height() {
-    let board = Board { red: 0, blue: 0, column_heights: [0, 6, 5, 3, 1, 6, 0], color_to_move: Color::Red, moves: MoveList::full(), winner: None };
+    let board = Board { red: 0, blue: 0, column_heights: [0, 6, 5, 3, 1, 6, 0, 0, 0], color_to_move: Color::Red, moves: MoveList::full(BoardConfig::standard()), status: GameStatus::Ongoing, ..Board::new() };
     assert_eq!(board.height(0), 0);
     assert_eq!(board.height(1), 6);
     assert_eq!(board.height(3), 3);
@@ -183,56 +438,271 @@ fn test_height() {
 
 #[test]
 fn test_moves() {
-    let board = Board { red: 0, blue: 0, column_heights: [0, 6, 5, 3, 1, 6, 0], color_to_move: Color::Red, moves: MoveList::full(), winner: None };
-    assert_eq!(board.compute_moves(), vec!(3, 2, 4, 0, 6));
+    let board = Board { red: 0, blue: 0, column_heights: [0, 6, 5, 3, 1, 6, 0, 0, 0], color_to_move: Color::Red, moves: MoveList::full(BoardConfig::standard()), status: GameStatus::Ongoing, ..Board::new() };
+    assert_eq!(board.compute_moves().moves(), vec!(3, 2, 4, 0, 6));
 }
 
 #[test]
 fn test_play_move() {
     let mut board = Board::new();
     board.red = 8;
-    board.column_heights = [0, 0, 0, 1, 0, 0, 0];
+    board.column_heights = [0, 0, 0, 1, 0, 0, 0, 0, 0];
 
-    board.play_move(3, false);
+    board.play_move(3);
     assert_eq!(board.color_to_move, Color::Blue);
 
-    board.play_move(1, false);
+    board.play_move(1);
     assert_eq!(board.color_to_move, Color::Red);
 
     assert_eq!(board.blue, 2);
     assert_eq!(board.red, 2056);
-    assert_eq!(board.column_heights, [0, 1, 0, 2, 0, 0, 0]);
+    assert_eq!(board.column_heights, [0, 1, 0, 2, 0, 0, 0, 0, 0]);
 }
 
 #[test]
 fn test_play_moves() {
     let mut board = Board::new();
     board.red = 8;
-    board.column_heights = [0, 0, 0, 1, 0, 0, 0];
+    board.column_heights = [0, 0, 0, 1, 0, 0, 0, 0, 0];
     board.color_to_move = Color::Blue;
 
-    board.play_moves(&vec!(3, 1));
+    board.play_moves(&[3, 1]);
 
     assert_eq!(board.color_to_move, Color::Blue);
     assert_eq!(board.blue, 2048);
     assert_eq!(board.red, 10);
-    assert_eq!(board.column_heights, [0, 1, 0, 2, 0, 0, 0]);
-    assert_eq!(board.winner, None);
+    assert_eq!(board.column_heights, [0, 1, 0, 2, 0, 0, 0, 0, 0]);
+    assert_eq!(board.winner(), None);
 }
 
 #[test]
 fn test_winner() {
     let mut board = Board::new();
-    board.play_moves(&vec!(4, 3, 4, 3, 4, 3, 4));
-    assert_eq!(board.winner, Some(Color::Red));
+    board.play_moves(&[4, 3, 4, 3, 4, 3, 4]);
+    assert_eq!(board.winner(), Some(Color::Red));
 
     board.reset();
     board.color_to_move = Color::Blue;
-    board.play_moves(&vec!(3, 4, 4, 3, 3, 4, 4, 3, 1, 2, 2));
-    assert_eq!(board.winner, Some(Color::Blue));
+    board.play_moves(&[3, 4, 4, 3, 3, 4, 4, 3, 1, 2, 2]);
+    assert_eq!(board.winner(), Some(Color::Blue));
 
     board.reset();
     board.color_to_move = Color::Blue;
-    board.play_moves(&vec!(3, 4, 4, 3, 3, 4, 4, 3, 1, 1, 2, 2));
-    assert_eq!(board.winner, None);
+    board.play_moves(&[3, 4, 4, 3, 3, 4, 4, 3, 1, 1, 2, 2]);
+    assert_eq!(board.winner(), None);
+}
+
+#[test]
+fn test_undo_move() {
+    let mut board = Board::new();
+    board.play_moves(&[3, 3, 2]);
+    let before = board;
+
+    board.play_move(4);
+    assert_eq!(board.played_moves(), &[3, 3, 2, 4]);
+    assert_eq!(board.undo_move(), Some(4));
+    assert_eq!(board, before);
+
+    assert_eq!(board.undo_moves(5), 3);
+    assert_eq!(board, Board::new());
+    assert_eq!(board.undo_move(), None);
+}
+
+#[test]
+fn test_undo_winning_move() {
+    let mut board = Board::new();
+    board.play_moves(&[4, 3, 4, 3, 4, 3]);
+    let before = board;
+
+    board.play_move(4);
+    assert_eq!(board.winner(), Some(Color::Red));
+    assert_eq!(board.undo_move(), Some(4));
+    assert_eq!(board, before);
+    assert_eq!(board.winner(), None);
+}
+
+#[test]
+fn test_undo_full_column() {
+    let mut board = Board::new();
+    board.play_moves(&[0, 0, 0, 0, 0, 0]);
+    assert_eq!(board.moves.moves(), vec!(3, 2, 4, 1, 5, 6));
+
+    board.undo_move();
+    assert_eq!(board.moves, MoveList::full(board.config));
+    assert_eq!(board.color_to_move, Color::Blue);
+    assert_eq!(board.height(0), 5);
+}
+
+#[test]
+fn test_row4_detection() {
+    let config = BoardConfig::standard();
+    let horizontal = config.position_mask(3, 2) | config.position_mask(4, 2) | config.position_mask(5, 2) | config.position_mask(6, 2);
+    assert!(Board::has_row4(horizontal));
+    assert!(Board::is_row4_at(horizontal, config.position_mask(4, 2)));
+    assert!(!Board::is_row4_at(horizontal | 1, 1));
+
+    // must not wrap around from the right edge into the next row
+    let wrapped = config.position_mask(5, 2) | config.position_mask(6, 2) | config.position_mask(0, 3) | config.position_mask(1, 3);
+    assert!(!Board::has_row4(wrapped));
+    assert!(!Board::is_row4_at(wrapped, config.position_mask(6, 2)));
+
+    let diagonal = config.position_mask(3, 0) | config.position_mask(2, 1) | config.position_mask(1, 2) | config.position_mask(0, 3);
+    assert!(Board::has_row4(diagonal));
+    assert!(Board::is_row4_at(diagonal, config.position_mask(1, 2)));
+}
+
+#[test]
+fn test_is_winning_move() {
+    let mut board = Board::new();
+    board.play_moves(&[4, 3, 4, 3, 4, 3]);
+    assert!(board.is_winning_move(4, Color::Red));
+    assert!(board.is_winning_move(3, Color::Blue));
+    assert!(!board.is_winning_move(3, Color::Red));
+    assert!(!board.is_winning_move(2, Color::Blue));
+}
+
+#[test]
+fn test_hash() {
+    let mut board = Board::new();
+    assert_eq!(board.hash(), 0);
+
+    board.play_moves(&[3, 2, 4, 3]);
+    assert_ne!(board.hash(), 0);
+    assert_eq!(board.hash(), Board::compute_hash(board.red, board.blue));
+
+    let mut transposed = Board::new();
+    transposed.play_moves(&[4, 2, 3, 3]);
+    assert_eq!(transposed.hash(), board.hash());
+
+    let mut swapped = Board::new();
+    swapped.play_moves(&[2, 3, 3, 4]);
+    assert_ne!(swapped.hash(), board.hash());
+
+    let hash = board.hash();
+    board.play_move(5);
+    assert_ne!(board.hash(), hash);
+    board.undo_move();
+    assert_eq!(board.hash(), hash);
+}
+
+#[test]
+fn test_canonical_key() {
+    let mut board = Board::new();
+    board.play_moves(&[0, 1, 1, 3]);
+    assert_eq!(Board::compute_hash(board.config.mirror_bits(board.red), board.config.mirror_bits(board.blue)), board.mirrored_hash);
+
+    let mut mirrored = Board::new();
+    mirrored.play_moves(&[6, 5, 5, 3]);
+    assert_eq!(board.canonical_key(), mirrored.canonical_key());
+    assert_ne!(board.hash(), mirrored.hash());
+    assert_ne!(board.is_canonical_mirrored(), mirrored.is_canonical_mirrored());
+    assert_eq!(board.canonical_column(1), mirrored.canonical_column(5));
+
+    let mut symmetric = Board::new();
+    symmetric.play_moves(&[3, 3]);
+    assert_eq!(symmetric.canonical_key(), symmetric.hash());
+    assert!(!symmetric.is_canonical_mirrored());
+}
+
+#[test]
+fn test_connect3_board() {
+    let mut board = Board::with_config(BoardConfig::new(6, 5, 3).unwrap());
+    assert_eq!(board.moves.moves(), vec!(2, 3, 1, 4, 0, 5));
+
+    board.play_moves(&[2, 2, 3, 3]);
+    assert_eq!(board.winner(), None);
+    assert!(board.is_winning_move(1, Color::Red));
+    assert!(board.is_winning_move(4, Color::Red));
+    board.play_move(4);
+    assert_eq!(board.winner(), Some(Color::Red));
+
+    board.undo_move();
+    assert_eq!(board.winner(), None);
+    assert_eq!(format!("{}", board), ". . . . . . \n. . . . . . \n. . . . . . \n. . o o . . \n. . x x . . ");
+}
+
+#[test]
+fn test_wide_board() {
+    let mut board = Board::with_config(BoardConfig::new(9, 7, 4).unwrap());
+    board.play_moves(&[8, 8, 7, 7, 6, 6]);
+    assert!(board.is_winning_move(5, Color::Red));
+    assert!(!board.is_winning_move(0, Color::Red));
+
+    for _ in 0..7 {
+        board.play_move(0);
+    }
+    assert_eq!(board.moves.moves(), vec!(4, 3, 5, 2, 6, 1, 7, 8));
+
+    let mut mirrored = Board::with_config(board.config);
+    mirrored.play_moves(&[0, 0, 1, 1, 2, 2]);
+    for _ in 0..7 {
+        mirrored.play_move(8);
+    }
+    assert_eq!(board.canonical_key(), mirrored.canonical_key());
+}
+
+#[test]
+fn test_from_bits() {
+    let mut board = Board::new();
+    board.play_moves(&[3, 3, 4, 2, 4]);
+    let mut from_bits = Board::from_bits(board.config, board.red, board.blue);
+    assert_eq!(from_bits.played_moves(), &[]);
+    assert_eq!(from_bits.column_heights, board.column_heights);
+    assert_eq!(from_bits.color_to_move, Color::Blue);
+    assert_eq!(from_bits.hash(), board.hash());
+    assert_eq!(from_bits.canonical_key(), board.canonical_key());
+
+    from_bits.play_move(4);
+    board.play_move(4);
+    assert_eq!(from_bits.moves, board.moves);
+    assert_eq!(from_bits.hash(), board.hash());
+}
+
+#[test]
+fn test_try_play_move() {
+    let mut board = Board::new();
+    assert_eq!(board.try_play_move(7), Err(MoveError::ColumnOutOfRange));
+    assert_eq!(board, Board::new());
+
+    for _ in 0..6 {
+        assert_eq!(board.try_play_move(0), Ok(()));
+    }
+    assert_eq!(board.try_play_move(0), Err(MoveError::ColumnFull));
+    assert_eq!(board.moves.moves(), vec!(3, 2, 4, 1, 5, 6));
+
+    board.play_moves(&[1, 2, 1, 2, 1, 2]);
+    assert_eq!(board.try_play_move(1), Ok(()));
+    assert_eq!(board.winner(), Some(Color::Red));
+    assert_eq!(board.try_play_move(3), Err(MoveError::GameOver));
+    assert_eq!(board.played_moves().len(), 13);
+}
+
+#[test]
+fn test_draw() {
+    let mut board = Board::new();
+    board.play_moves(&[0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 2, 3, 2, 3, 2, 3, 3, 2, 3, 2, 3, 2, 4, 5, 4, 5, 4, 5, 5, 4, 5, 4, 5, 4, 6, 6, 6, 6, 6]);
+    assert_eq!(board.status, GameStatus::Ongoing);
+
+    board.play_move(6);
+    assert_eq!(board.status, GameStatus::Draw);
+    assert_eq!(board.winner(), None);
+    assert_eq!(board.winning_line(), None);
+    assert_eq!(board.try_play_move(6), Err(MoveError::GameOver));
+
+    board.undo_move();
+    assert_eq!(board.status, GameStatus::Ongoing);
+}
+
+#[test]
+fn test_winning_line() {
+    let mut board = Board::new();
+    assert_eq!(board.winning_line(), None);
+
+    board.play_moves(&[3, 3, 4, 4, 5, 5, 6]);
+    assert_eq!(board.status, GameStatus::Won(Color::Red));
+    assert_eq!(board.winning_line(), Some(vec!((3, 0), (4, 0), (5, 0), (6, 0))));
+
+    board.reset();
+    board.play_moves(&[0, 1, 1, 2, 2, 3, 2, 3, 3, 6, 3]);
+    assert_eq!(board.winning_line(), Some(vec!((0, 0), (1, 1), (2, 2), (3, 3))));
 }
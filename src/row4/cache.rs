@@ -1,62 +1,156 @@
 use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::{Mutex, MutexGuard};
 
+use row4::*;
 use row4::board::Board;
+use row4::transposition::{self, TranspositionTable};
 
+// a power of two, so that the shard follows from some bits of the key
+const NUM_SHARDS: usize = 64;
+
+/// the size of the transposition table of a new cache
+pub const DEFAULT_TABLE_MEGABYTES: usize = 16;
+
+type Shard = HashMap<u64, f64, BuildHasherDefault<ZobristHasher>>;
+
+/// Evaluations of positions, which can be shared between search threads.
+///
+/// Leaf evaluations are split up into shards with a lock each, so that threads rarely
+/// wait for each other. The results of searched positions go to a transposition table.
+/// Both are kept apart for the two colors, as evaluations are from the view of one side.
 pub struct BoardCache {
-    // the key is the red/blue board encoding
-    cache: HashMap<(u64, u64), f64>
+    // the key is the canonical Zobrist key of the board, mixed with the color it is evaluated for
+    shards: Vec<Mutex<Shard>>,
+    table: TranspositionTable,
+}
+
+impl Default for BoardCache {
+    fn default() -> BoardCache {
+        BoardCache::new()
+    }
 }
 
 impl BoardCache {
     pub fn new() -> BoardCache {
+        BoardCache::with_table_megabytes(DEFAULT_TABLE_MEGABYTES)
+    }
+
+    pub fn with_table_megabytes(megabytes: usize) -> BoardCache {
         BoardCache {
-            cache: HashMap::new()
+            shards: (0..NUM_SHARDS).map(|_| Mutex::new(HashMap::default())).collect(),
+            table: TranspositionTable::new(megabytes),
         }
     }
 
-    pub fn store(&mut self, board: &Board, eval: f64, store_mirrors: bool) {
-        self.cache.insert((board.red, board.blue), eval);
+    /// the results of searched positions
+    pub fn table(&self) -> &TranspositionTable {
+        &self.table
+    }
+
+    fn shard(&self, key: u64) -> MutexGuard<'_, Shard> {
+        // the lower bits select the bucket within the shard
+        self.shards[(key >> 40) as usize & (NUM_SHARDS - 1)].lock().unwrap()
+    }
+
+    /// stores the evaluation of a position for the given color
+    ///
+    /// A position and its left/right mirror image share one entry, as both evaluate the same.
+    pub fn store(&self, board: &Board, own_color: Color, eval: f64) {
+        let key = BoardCache::key(board, own_color);
+        self.shard(key).insert(key, eval);
+    }
 
-        if store_mirrors {
-            self.cache.insert((board.blue, board.red), 1.0 - eval); // switched colors, pessimistic eval approximation (due to draws)
+    /// looks up the evaluation of a position or of its mirror image for the given color
+    ///
+    /// Evaluations don't change under mirroring, so a value stored for the mirror image
+    /// can be returned as is.
+    pub fn get(&self, board: &Board, own_color: Color) -> Option<f64> {
+        let key = BoardCache::key(board, own_color);
+        self.shard(key).get(&key).cloned()
+    }
 
-            let mirrored_red = BoardCache::mirror(board.red);
-            let mirrored_blue = BoardCache::mirror(board.blue);
-            self.cache.insert((mirrored_red, mirrored_blue), eval);
-            self.cache.insert((mirrored_blue, mirrored_red), 1.0 - eval);
+    // evaluations for one color can't be turned into those for the other, as draws count for neither
+    fn key(board: &Board, own_color: Color) -> u64 {
+        match own_color {
+            Color::Red => board.canonical_key(),
+            Color::Blue => board.canonical_key() ^ transposition::BLUE_KEY
         }
     }
 
-    pub fn get(&self, board: &Board) -> Option<&f64> {
-        self.cache.get(&(board.red, board.blue))
+    /// the number of stored leaf evaluations
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
     }
 
-    /// mirror the board bit representation
-    fn mirror(src: u64) -> u64 {
-        let mut src_mask = 1u64 << (6 + 8 * 5);
-        let mut target_mask = 1 << (8 * 5);
-        let mut target = 0;
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
 
-        for _row in 0..6 {
-            for _column in 0..7 {
-                if src & src_mask != 0 {
-                    target |= target_mask;
-                }
-                src_mask >>= 1;
-                target_mask <<= 1;
-            }
-            src_mask >>= 1;
-            target_mask >>= 15;
+/// Zobrist hashes are already evenly distributed, so they are used as hash codes directly.
+#[derive(Default)]
+pub struct ZobristHasher(u64);
+
+impl Hasher for ZobristHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ byte as u64;
         }
-        target
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
     }
 }
 
 #[test]
-fn test_mirror() {
-    assert_eq!(BoardCache::mirror(0), 0);
-    assert_eq!(BoardCache::mirror(1), 1 << 6);
-    assert_eq!(BoardCache::mirror(1 | 1 << 1 | 1 << 6), 1 | 1 << 5 | 1 << 6);
-    assert_eq!(BoardCache::mirror(525324), 528408);
-    assert_eq!(BoardCache::mirror(134219792), 134219780);
+fn test_store_mirrors() {
+    let mut board = Board::new();
+    board.play_moves(&[0, 1, 1]);
+    let cache = BoardCache::new();
+    cache.store(&board, Color::Red, 0.75);
+
+    let mut mirrored = Board::new();
+    mirrored.play_moves(&[6, 5, 5]);
+    assert_eq!(cache.get(&board, Color::Red), Some(0.75));
+    assert_eq!(cache.get(&mirrored, Color::Red), Some(0.75));
+    assert_eq!(cache.get(&Board::new(), Color::Red), None);
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn test_store_per_color() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let cache = BoardCache::new();
+    cache.store(&board, Color::Red, 0.75);
+    assert_eq!(cache.get(&board, Color::Blue), None);
+
+    cache.store(&board, Color::Blue, 0.2);
+    assert_eq!(cache.get(&board, Color::Red), Some(0.75));
+    assert_eq!(cache.get(&board, Color::Blue), Some(0.2));
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn test_shared_between_threads() {
+    use std::thread;
+
+    let cache = BoardCache::new();
+    thread::scope(|scope| {
+        for column in 0..7 {
+            let cache = &cache;
+            scope.spawn(move || {
+                let mut board = Board::new();
+                board.play_move(column);
+                cache.store(&board, Color::Red, column as f64);
+            });
+        }
+    });
+    // mirrored moves share an entry
+    assert_eq!(cache.len(), 4);
 }
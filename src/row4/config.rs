@@ -0,0 +1,239 @@
+use row4::*;
+
+/// Dimensions and winning line length of a board.
+///
+/// Each row of the board takes up 8 bits of the bit arrays, or `width` bits for
+/// boards wider than 8 columns, so at most 64 positions fit.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct BoardConfig {
+    width: u8,
+    height: u8,
+    connect: u8,
+    // columns sorted from the centre outwards, only the first `width` entries are used
+    columns: [Column; MAX_WIDTH],
+}
+
+impl BoardConfig {
+    /// the classic 7x6 connect four board
+    pub fn standard() -> BoardConfig {
+        BoardConfig::new(7, 6, 4).unwrap()
+    }
+
+    /// creates a board config with the given dimensions, where `connect` pieces in a row win
+    /// returns None if the board doesn't fit into the 64 bit arrays or the line can't fit on the board
+    pub fn new(width: u8, height: u8, connect: u8) -> Option<BoardConfig> {
+        if width == 0 || width as usize > MAX_WIDTH || height == 0 || connect < 2 || connect > u8::max(width, height) {
+            return None;
+        }
+        if BoardConfig::stride_for(width) as usize * height as usize > 64 {
+            return None;
+        }
+
+        let mut columns = [0; MAX_WIDTH];
+        let centre = (width - 1) / 2;
+        for (index, column) in columns.iter_mut().take(width as usize).enumerate() {
+            let offset = (index as u8).div_ceil(2);
+            // on even widths, the centre is left of the middle, so go right first
+            let go_left = (index % 2 == 1) == (width % 2 == 1);
+            *column = if go_left { centre - offset } else { centre + offset };
+        }
+
+        Some(BoardConfig { width, height, connect, columns })
+    }
+
+    fn stride_for(width: u8) -> u8 {
+        u8::max(8, width)
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    pub fn connect(&self) -> u8 {
+        self.connect
+    }
+
+    /// the number of positions on the board
+    pub fn size(&self) -> u8 {
+        self.width * self.height
+    }
+
+    /// the number of bits used per row
+    pub fn stride(&self) -> u8 {
+        BoardConfig::stride_for(self.width)
+    }
+
+    /// checks if this is the classic 7x6 connect four board, which has its own fast path
+    pub fn is_standard(&self) -> bool {
+        self.width == 7 && self.height == 6 && self.connect == 4
+    }
+
+    /// all columns, starting at the centre and moving outwards
+    pub fn columns(&self) -> &[Column] {
+        &self.columns[..self.width as usize]
+    }
+
+    /// utility to complete a bit mask from a given position
+    pub fn position_mask(&self, column: Column, height: u8) -> u64 {
+        1u64 << (height * self.stride() + column)
+    }
+
+    /// returns the column at the mirrored position
+    pub fn mirror_column(&self, column: Column) -> Column {
+        self.width - 1 - column
+    }
+
+    /// mirror the board bit representation
+    pub fn mirror_bits(&self, src: u64) -> u64 {
+        let mut target = 0;
+        for row in 0..self.height {
+            for column in 0..self.width {
+                if src & self.position_mask(column, row) != 0 {
+                    target |= self.position_mask(self.mirror_column(column), row);
+                }
+            }
+        }
+        target
+    }
+
+    /// checks if the bits contain a winning line through the given position (which has to be part of the bits)
+    ///
+    /// This walks along the board coordinates, so that it works for any board size.
+    pub fn is_line_at(&self, bits: u64, column: Column, height: u8) -> bool {
+        for &(column_step, row_step) in &[(1i8, 0i8), (1, 1), (1, -1), (0, 1)] {
+            let mut count = 1;
+            for &direction in &[1i8, -1] {
+                let mut c = column as i8 + direction * column_step;
+                let mut r = height as i8 + direction * row_step;
+                while c >= 0 && c < self.width as i8 && r >= 0 && r < self.height as i8
+                        && bits & self.position_mask(c as Column, r as u8) != 0 {
+                    count += 1;
+                    c += direction * column_step;
+                    r += direction * row_step;
+                }
+            }
+            if count >= self.connect {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// returns the (column, row) positions of a winning line within the bits, if there is one
+    pub fn find_line(&self, bits: u64) -> Option<Vec<(Column, u8)>> {
+        let contains = |c: i8, r: i8| c >= 0 && c < self.width as i8 && r >= 0 && r < self.height as i8
+                && bits & self.position_mask(c as Column, r as u8) != 0;
+        for row in 0..self.height as i8 {
+            for column in 0..self.width as i8 {
+                for &(column_step, row_step) in &[(1i8, 0i8), (1, 1), (1, -1), (0, 1)] {
+                    // only start at the first position of a line
+                    if !contains(column, row) || contains(column - column_step, row - row_step) {
+                        continue;
+                    }
+                    let mut line = Vec::new();
+                    let (mut c, mut r) = (column, row);
+                    while contains(c, r) {
+                        line.push((c as Column, r as u8));
+                        c += column_step;
+                        r += row_step;
+                    }
+                    if line.len() >= self.connect as usize {
+                        return Some(line);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// checks if the bits contain a winning line anywhere
+    pub fn has_line(&self, bits: u64) -> bool {
+        for row in 0..self.height {
+            for column in 0..self.width {
+                if bits & self.position_mask(column, row) != 0 && self.is_line_at(bits, column, row) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Default for BoardConfig {
+    fn default() -> BoardConfig {
+        BoardConfig::standard()
+    }
+}
+
+#[test]
+fn test_new() {
+    assert!(BoardConfig::new(6, 5, 3).is_some());
+    assert!(BoardConfig::new(8, 7, 5).is_some());
+    assert!(BoardConfig::new(9, 7, 4).is_some());
+    assert!(BoardConfig::new(9, 8, 4).is_none());
+    assert!(BoardConfig::new(10, 6, 4).is_none());
+    assert!(BoardConfig::new(4, 4, 5).is_none());
+    assert!(BoardConfig::new(7, 6, 1).is_none());
+}
+
+#[test]
+fn test_columns() {
+    assert_eq!(BoardConfig::standard().columns(), &COLUMNS);
+    assert_eq!(BoardConfig::new(6, 5, 4).unwrap().columns(), &[2, 3, 1, 4, 0, 5]);
+    assert_eq!(BoardConfig::new(9, 7, 4).unwrap().columns(), &[4, 3, 5, 2, 6, 1, 7, 0, 8]);
+}
+
+#[test]
+fn test_position_mask() {
+    let config = BoardConfig::standard();
+    assert_eq!(config.position_mask(3, 1), 2048);
+    assert_eq!(config.position_mask(6, 5), 1 << (8 * 5 + 6));
+    assert_eq!(BoardConfig::new(9, 7, 4).unwrap().position_mask(8, 6), 1 << 62);
+}
+
+#[test]
+fn test_mirror_bits() {
+    let config = BoardConfig::standard();
+    assert_eq!(config.mirror_bits(0), 0);
+    assert_eq!(config.mirror_bits(1), 1 << 6);
+    assert_eq!(config.mirror_bits(1 | 1 << 1 | 1 << 6), 1 | 1 << 5 | 1 << 6);
+    assert_eq!(config.mirror_bits(525324), 528408);
+    assert_eq!(config.mirror_bits(134219792), 134219780);
+
+    let wide = BoardConfig::new(9, 7, 4).unwrap();
+    assert_eq!(wide.mirror_bits(1 | 1 << 10), 1 << 8 | 1 << 16);
+}
+
+#[test]
+fn test_is_line_at() {
+    let config = BoardConfig::new(9, 7, 4).unwrap();
+    let mask = |positions: &[(Column, u8)]| positions.iter().fold(0, |bits, &(c, r)| bits | config.position_mask(c, r));
+
+    let horizontal = mask(&[(5, 2), (6, 2), (7, 2), (8, 2)]);
+    assert!(config.is_line_at(horizontal, 8, 2));
+    assert!(config.has_line(horizontal));
+
+    // must not wrap around from the right edge into the next row
+    let wrapped = mask(&[(6, 2), (7, 2), (8, 2), (0, 3)]);
+    assert!(!config.is_line_at(wrapped, 8, 2));
+    assert!(!config.has_line(wrapped));
+
+    let diagonal = mask(&[(8, 0), (7, 1), (6, 2), (5, 3)]);
+    assert!(config.is_line_at(diagonal, 7, 1));
+
+    let connect3 = BoardConfig::new(6, 5, 3).unwrap();
+    assert!(connect3.has_line(0b111 << 8));
+    assert!(!connect3.has_line(0b11011 << 8));
+}
+
+#[test]
+fn test_find_line() {
+    let config = BoardConfig::new(6, 5, 3).unwrap();
+    assert_eq!(config.find_line(0b11011 << 8), None);
+    assert_eq!(config.find_line(0b1111 << 8), Some(vec!((0, 1), (1, 1), (2, 1), (3, 1))));
+    assert_eq!(config.find_line(1 << 2 | 1 << 9 | 1 << 16), Some(vec!((0, 2), (1, 1), (2, 0))));
+}
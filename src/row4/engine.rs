@@ -0,0 +1,467 @@
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::Duration;
+
+use rand::{thread_rng, Rng};
+
+use row4::*;
+use row4::board::{Board, GameStatus};
+use row4::cache::BoardCache;
+use row4::evaluator::Evaluator;
+use row4::heuristic::Heuristic;
+use row4::mcts::Mcts;
+use row4::score::Score;
+use row4::monte_carlo::MonteCarlo;
+use row4::minmax::{self, PrintObserver, SearchHandle, SearchObserver, SearchOptions, SilentObserver};
+use row4::time::Timer;
+use row4::solver::{Outcome, Solver};
+
+#[cfg(test)]
+use row4::config::BoardConfig;
+
+/// names of all engines that can be created with `engine_by_name`
+pub const ENGINE_NAMES: [&str; 6] = ["minmax", "heuristic", "mcts", "random", "greedy", "solver"];
+
+/// How long an engine may think about its move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SearchLimits {
+    pub millis: u64,
+}
+
+impl SearchLimits {
+    pub fn millis(millis: u64) -> SearchLimits {
+        SearchLimits { millis }
+    }
+}
+
+impl Default for SearchLimits {
+    fn default() -> SearchLimits {
+        SearchLimits::millis(5_000)
+    }
+}
+
+/// The move chosen by an engine, together with what it expects to happen afterwards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best: Column,
+    // the expected continuation in playing order, starting with the best move
+    pub variant: Vec<Column>,
+    // the expected result for the side to move
+    pub eval: Score,
+    // the number of positions or playouts the engine looked at
+    pub nodes: u64,
+}
+
+impl SearchResult {
+    fn single_move(column: Column, eval: Score) -> SearchResult {
+        SearchResult { best: column, variant: vec!(column), eval, nodes: 0 }
+    }
+}
+
+/// Anything that can choose a move.
+pub trait Engine {
+    fn name(&self) -> &'static str;
+
+    /// chooses a move on the given board, or returns None if the game is over
+    fn choose_move(&mut self, board: &Board, limits: SearchLimits) -> Option<SearchResult>;
+
+    /// starts thinking on the opponent's time, after the engine's own move led to the given board
+    ///
+    /// The next call to `choose_move` makes use of the work if the opponent played the
+    /// expected move, and discards it otherwise. Engines that can't ponder ignore this.
+    fn ponder(&mut self, _board: &Board) {}
+}
+
+/// creates a new engine with the given name, one of `ENGINE_NAMES`
+pub fn engine_by_name(name: &str) -> Option<Box<dyn Engine>> {
+    match name {
+        "minmax" => {
+            let num_threads = monte_carlo::default_num_threads();
+            let mut engine = MinMaxEngine::with_observer(MonteCarlo::new().with_threads(num_threads), Box::new(PrintObserver));
+            engine.set_num_threads(num_threads);
+            Some(Box::new(engine))
+        },
+        // minmax with the static evaluation, which is fast enough that a single thread will do
+        "heuristic" => {
+            let mut engine = MinMaxEngine::with_observer(Heuristic::new(), Box::new(PrintObserver));
+            engine.name = "heuristic";
+            Some(Box::new(engine))
+        },
+        "mcts" => Some(Box::new(Mcts::new(&Board::new()))),
+        "random" => Some(Box::new(RandomEngine)),
+        "greedy" => Some(Box::new(GreedyEngine)),
+        "solver" => Some(Box::new(Solver::new())),
+        _ => None
+    }
+}
+
+/// plays a game between two engines from the given board and returns the final board
+pub fn play_match(red: &mut dyn Engine, blue: &mut dyn Engine, mut board: Board, limits: SearchLimits) -> Board {
+    loop {
+        let result = match board.color_to_move {
+            Color::Red => red.choose_move(&board, limits),
+            Color::Blue => blue.choose_move(&board, limits)
+        };
+        match result {
+            Some(result) => board.play_move(result.best),
+            None => return board
+        }
+    }
+}
+
+// long enough to never run out while the opponent thinks
+const PONDER_MILLIS: u64 = 24 * 60 * 60 * 1_000;
+
+/// Iterative deepening minmax, evaluating the leaves with the given evaluator.
+///
+/// The cache of evaluated positions is kept from move to move. While pondering, the
+/// search runs in the background on the board after the opponent's expected reply.
+pub struct MinMaxEngine {
+    name: &'static str,
+    cache: BoardCache,
+    // shared with the search while pondering
+    evaluator: Arc<dyn Evaluator>,
+    observer: Box<dyn SearchObserver>,
+    // the main variant of the last search, in playing order
+    last_variant: Vec<Column>,
+    // the board that is pondered on, with the search that owns the cache meanwhile
+    pondering: Option<(Board, SearchHandle)>,
+    ponder_hits: u32,
+    ponder_misses: u32,
+    options: SearchOptions,
+}
+
+impl MinMaxEngine {
+    /// creates an engine that doesn't report its progress
+    pub fn new<E: Evaluator + 'static>(evaluator: E) -> MinMaxEngine {
+        MinMaxEngine::with_observer(evaluator, Box::new(SilentObserver))
+    }
+
+    pub fn with_observer<E: Evaluator + 'static>(evaluator: E, observer: Box<dyn SearchObserver>) -> MinMaxEngine {
+        MinMaxEngine {
+            name: "minmax",
+            cache: BoardCache::new(),
+            evaluator: Arc::new(evaluator),
+            observer,
+            last_variant: vec!(),
+            pondering: None,
+            ponder_hits: 0,
+            ponder_misses: 0,
+            options: SearchOptions::default(),
+        }
+    }
+
+    /// searches the moves at the root on the given number of threads, 1 by default
+    pub fn set_num_threads(&mut self, num_threads: usize) {
+        self.options.num_threads = usize::max(1, num_threads);
+    }
+
+    /// switches the search techniques, to compare the engine with and without them
+    ///
+    /// Options that don't go together are normalized, see `SearchOptions::normalized`.
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options.normalized();
+    }
+
+    /// the number of times the opponent played the move that was pondered on
+    pub fn ponder_hits(&self) -> u32 {
+        self.ponder_hits
+    }
+
+    /// the number of times the opponent played another move than the one that was pondered on
+    pub fn ponder_misses(&self) -> u32 {
+        self.ponder_misses
+    }
+
+    /// stops a running ponder search and takes back the cache it owns
+    fn stop_pondering(&mut self) {
+        if let Some((_, handle)) = self.pondering.take() {
+            handle.stop();
+            self.cache = handle.join().cache;
+        }
+    }
+
+    /// lets a ponder search on the given board continue for the given time and takes over its result
+    fn finish_ponder_hit(&mut self, handle: SearchHandle, millis: u64) -> (Vec<Column>, Score, u64) {
+        let timer = Timer::new();
+        while !handle.is_finished() && timer.elapsed_millis() < millis {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.stop();
+        if let Some(info) = handle.best_so_far() {
+            self.observer.on_iteration(&info);
+        }
+        let finished = handle.join();
+        self.cache = finished.cache;
+        (finished.variant, finished.eval, finished.positions)
+    }
+}
+
+impl Engine for MinMaxEngine {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn choose_move(&mut self, board: &Board, limits: SearchLimits) -> Option<SearchResult> {
+        if board.status != GameStatus::Ongoing {
+            self.stop_pondering();
+            return None;
+        }
+        if self.pondering.as_ref().is_some_and(|(ponder_board, _)| ponder_board != board) {
+            // the evaluations in the cache are still valid, only the variant is of no use
+            self.ponder_misses += 1;
+            self.stop_pondering();
+        }
+        let pondered = self.pondering.take().map(|(_, handle)| {
+            self.ponder_hits += 1;
+            self.finish_ponder_hit(handle, limits.millis)
+        });
+
+        let (mut variant, eval, positions) = pondered.unwrap_or_else(|| {
+            let (variant, eval, _, positions) = minmax::iterative_minmax(board, board.color_to_move, limits.millis,
+                &self.cache, &*self.evaluator, &mut *self.observer, &AtomicBool::new(false), self.options);
+            (variant, eval, positions)
+        });
+        variant.reverse();
+        self.last_variant = variant.clone();
+        Some(SearchResult { best: variant[0], variant, eval, nodes: positions })
+    }
+
+    fn ponder(&mut self, board: &Board) {
+        self.stop_pondering();
+        let reply = match self.last_variant.get(1) {
+            Some(&reply) if board.status == GameStatus::Ongoing && board.played_moves().last() == self.last_variant.first() => reply,
+            _ => return
+        };
+
+        let mut ponder_board = *board;
+        ponder_board.play_move(reply);
+        if ponder_board.status != GameStatus::Ongoing {
+            return;
+        }
+        let cache = mem::take(&mut self.cache);
+        let handle = SearchHandle::spawn(&ponder_board, ponder_board.color_to_move, PONDER_MILLIS, cache, Arc::clone(&self.evaluator),
+                                         Box::new(SilentObserver), self.options);
+        self.pondering = Some((ponder_board, handle));
+    }
+}
+
+/// Waits for a running ponder search, so that it doesn't outlive the engine.
+impl Drop for MinMaxEngine {
+    fn drop(&mut self) {
+        self.stop_pondering();
+    }
+}
+
+impl Engine for Mcts {
+    fn name(&self) -> &'static str {
+        "mcts"
+    }
+
+    fn choose_move(&mut self, board: &Board, limits: SearchLimits) -> Option<SearchResult> {
+        self.advance_to(board);
+        let result = self.search(limits.millis)?;
+        let win_rate = result.stats.first()?.win_rate;
+        Some(SearchResult { best: result.best, variant: vec!(result.best), eval: Score::estimate(win_rate), nodes: result.playouts })
+    }
+}
+
+/// Plays the perfect move if it is found within the time limit, and the most central move that
+/// doesn't lose right away otherwise. Only supports the standard board.
+impl Engine for Solver {
+    fn name(&self) -> &'static str {
+        "solver"
+    }
+
+    fn choose_move(&mut self, board: &Board, limits: SearchLimits) -> Option<SearchResult> {
+        if board.status != GameStatus::Ongoing || !board.config.is_standard() {
+            return None;
+        }
+        let nodes = self.nodes();
+        self.set_time_limit(Some(limits.millis));
+        let best = self.best_move(board);
+        self.set_time_limit(None);
+        let (column, eval) = match best {
+            Some((column, Outcome::Win(plies))) => (column, Score::win_at(board.num_pieces() + plies)),
+            Some((column, Outcome::Draw)) => (column, Score::DRAW),
+            Some((column, Outcome::Loss(plies))) => (column, Score::loss_at(board.num_pieces() + plies)),
+            None => (monte_carlo::useful_moves(board)[0], Score::estimate(0.5))
+        };
+        Some(SearchResult { nodes: self.nodes() - nodes, ..SearchResult::single_move(column, eval) })
+    }
+}
+
+/// Plays any legal move at random.
+pub struct RandomEngine;
+
+impl Engine for RandomEngine {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn choose_move(&mut self, board: &Board, _limits: SearchLimits) -> Option<SearchResult> {
+        if board.status != GameStatus::Ongoing {
+            return None;
+        }
+        let column = *thread_rng().choose(&board.moves.moves())?;
+        Some(SearchResult::single_move(column, Score::estimate(0.5)))
+    }
+}
+
+/// Wins right away if possible and blocks the opponent's immediate wins, otherwise
+/// plays the most central column.
+pub struct GreedyEngine;
+
+impl Engine for GreedyEngine {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn choose_move(&mut self, board: &Board, _limits: SearchLimits) -> Option<SearchResult> {
+        if board.status != GameStatus::Ongoing {
+            return None;
+        }
+        let column = *monte_carlo::useful_moves(board).first()?;
+        let eval = if board.is_winning_move(column, board.color_to_move) {
+            Score::win_at(board.num_pieces() + 1)
+        } else {
+            Score::estimate(0.5)
+        };
+        Some(SearchResult::single_move(column, eval))
+    }
+}
+
+#[test]
+fn test_engine_by_name() {
+    for &name in ENGINE_NAMES.iter() {
+        assert_eq!(engine_by_name(name).unwrap().name(), name);
+    }
+    assert!(engine_by_name("deep thought").is_none());
+}
+
+#[test]
+fn test_engines_find_win() {
+    let board = Board::from_moves_str("445566").unwrap();
+    let mut engines: Vec<Box<dyn Engine>> = vec!(
+        Box::new(MinMaxEngine::new(MonteCarlo::new())),
+        Box::new(MinMaxEngine::new(Heuristic::new())),
+        Box::new(Mcts::new(&board)),
+        Box::new(GreedyEngine),
+    );
+    for engine in &mut engines {
+        let result = engine.choose_move(&board, SearchLimits::millis(50)).unwrap();
+        assert_eq!(result.best, 2, "{}", engine.name());
+        assert_eq!(result.variant[0], 2, "{}", engine.name());
+        assert_eq!(result.eval.win_rate(), 1.0, "{}", engine.name());
+    }
+}
+
+#[test]
+fn test_solver_engine() {
+    let board = Board::from_moves_str("445566").unwrap();
+    let mut solver = Solver::new();
+    let result = solver.choose_move(&board, SearchLimits::default()).unwrap();
+    assert_eq!(result.best, 2);
+    assert_eq!(result.eval, Score::win_at(7));
+    assert!(result.nodes > 0);
+
+    // without the time to solve the position, it falls back to the centre
+    let result = solver.choose_move(&Board::new(), SearchLimits::millis(10)).unwrap();
+    assert_eq!((result.best, result.eval), (3, Score::estimate(0.5)));
+
+    // other boards are not supported
+    let board = Board::with_config(BoardConfig::new(5, 4, 3).unwrap());
+    assert_eq!(solver.choose_move(&board, SearchLimits::default()), None);
+}
+
+#[test]
+fn test_both_colors() {
+    // red thinks it is doing well and blue badly, which the cache must not mix up
+    let mut engine = MinMaxEngine::new(|_board: &Board, own_color: Color| (if own_color == Color::Red { 0.9 } else { 0.2 }, 0));
+    let mut board = Board::from_moves_str("4453").unwrap();
+    let result = engine.choose_move(&board, SearchLimits::millis(50)).unwrap();
+    assert_eq!(result.eval, Score::estimate(0.9));
+
+    // a single depth for blue only reaches positions red has evaluated already
+    board.play_move(result.best);
+    let result = engine.choose_move(&board, SearchLimits::millis(0)).unwrap();
+    assert_eq!(result.eval, Score::estimate(0.2));
+}
+
+#[test]
+fn test_ponder() {
+    let mut engine = MinMaxEngine::new(MonteCarlo::new());
+    let mut board = Board::new();
+
+    // pretend that a search expects 4 to be answered with 4
+    engine.last_variant = vec!(3, 3);
+    board.play_move(3);
+    engine.ponder(&board);
+    assert!(engine.pondering.is_some());
+    board.play_move(3);
+    let result = engine.choose_move(&board, SearchLimits::millis(20)).unwrap();
+    assert_eq!((engine.ponder_hits(), engine.ponder_misses()), (1, 0));
+    assert!(board.moves.moves().contains(&result.best));
+    assert!(!engine.cache.is_empty());
+
+    // any other reply is a miss, after which the engine searches as usual
+    engine.last_variant = vec!(2, 2);
+    board.play_move(2);
+    engine.ponder(&board);
+    board.play_move(4);
+    let result = engine.choose_move(&board, SearchLimits::millis(20)).unwrap();
+    assert_eq!((engine.ponder_hits(), engine.ponder_misses()), (1, 1));
+    assert!(board.moves.moves().contains(&result.best));
+    assert!(engine.pondering.is_none());
+
+    // nothing to ponder on without an expected reply
+    engine.last_variant = vec!(5);
+    board.play_move(5);
+    engine.ponder(&board);
+    assert!(engine.pondering.is_none());
+}
+
+#[test]
+fn test_ponder_twice() {
+    let mut engine = MinMaxEngine::new(MonteCarlo::new());
+    let mut board = Board::new();
+
+    // pondering again on the same board, as after a takeback, keeps the cache of the first search
+    engine.last_variant = vec!(3, 3);
+    board.play_move(3);
+    engine.ponder(&board);
+    thread::sleep(Duration::from_millis(20));
+    engine.ponder(&board);
+    assert!(engine.pondering.is_some());
+    engine.stop_pondering();
+    assert!(engine.pondering.is_none());
+    assert!(!engine.cache.is_empty());
+}
+
+#[test]
+fn test_engines_on_finished_games() {
+    let won = Board::from_moves_str("4455663").unwrap();
+    let mut drawn = Board::new();
+    drawn.play_moves(&[0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 2, 3, 2, 3, 2, 3, 3, 2, 3, 2, 3, 2, 4, 5, 4, 5, 4, 5, 5, 4, 5, 4, 5, 4, 6, 6, 6, 6, 6, 6]);
+    for &name in ENGINE_NAMES.iter() {
+        let mut engine = engine_by_name(name).unwrap();
+        for board in &[won, drawn] {
+            assert!(board.status != GameStatus::Ongoing);
+            assert_eq!(engine.choose_move(board, SearchLimits::millis(0)), None, "{}", name);
+        }
+        // there is always a move on a board that isn't finished yet, even without any time to think
+        let board = Board::from_moves_str("445566").unwrap();
+        let result = engine.choose_move(&board, SearchLimits::millis(0)).unwrap();
+        assert!(board.moves.moves().contains(&result.best), "{}", name);
+    }
+}
+
+#[test]
+fn test_play_match() {
+    let board = play_match(&mut GreedyEngine, &mut RandomEngine, Board::new(), SearchLimits::millis(0));
+    assert!(board.status != GameStatus::Ongoing);
+    assert_eq!(board.played_moves()[0], 3);
+}
+
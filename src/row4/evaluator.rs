@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use row4::*;
+use row4::board::Board;
+
+/// Estimates the value of the positions at the leaves of a search.
+///
+/// An evaluator is shared by all threads of a search, so any state it keeps, such as
+/// a thread pool, a random number generator or statistics, sits behind `&self`.
+pub trait Evaluator: Send + Sync {
+    /// the win rate of the position for the given color, with the number of moves played to find it
+    fn evaluate(&self, board: &Board, own_color: Color) -> (f64, u64);
+}
+
+/// Plain functions and closures evaluate positions by being called.
+impl<F> Evaluator for F where F: Fn(&Board, Color) -> (f64, u64) + Send + Sync {
+    fn evaluate(&self, board: &Board, own_color: Color) -> (f64, u64) {
+        self(board, own_color)
+    }
+}
+
+/// Counts the evaluations and played moves of another evaluator.
+pub struct Counting<E> {
+    inner: E,
+    evaluations: AtomicU64,
+    moves_played: AtomicU64,
+}
+
+impl<E: Evaluator> Counting<E> {
+    pub fn new(inner: E) -> Counting<E> {
+        Counting { inner, evaluations: AtomicU64::new(0), moves_played: AtomicU64::new(0) }
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn evaluations(&self) -> u64 {
+        self.evaluations.load(Ordering::Relaxed)
+    }
+
+    pub fn moves_played(&self) -> u64 {
+        self.moves_played.load(Ordering::Relaxed)
+    }
+}
+
+impl<E: Evaluator> Evaluator for Counting<E> {
+    fn evaluate(&self, board: &Board, own_color: Color) -> (f64, u64) {
+        let (eval, moves_played) = self.inner.evaluate(board, own_color);
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        self.moves_played.fetch_add(moves_played, Ordering::Relaxed);
+        (eval, moves_played)
+    }
+}
+
+#[test]
+fn test_counting() {
+    let counting = Counting::new(|_board: &Board, own_color: Color| (if own_color == Color::Red { 1.0 } else { 0.0 }, 7));
+    assert_eq!(counting.evaluate(&Board::new(), Color::Red), (1.0, 7));
+    assert_eq!(counting.evaluate(&Board::new(), Color::Blue), (0.0, 7));
+    assert_eq!((counting.evaluations(), counting.moves_played()), (2, 14));
+}
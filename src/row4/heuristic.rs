@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use row4::*;
+use row4::board::Board;
+use row4::config::BoardConfig;
+use row4::evaluator::Evaluator;
+
+#[cfg(test)]
+use std::sync::atomic::AtomicBool;
+#[cfg(test)]
+use row4::cache::BoardCache;
+#[cfg(test)]
+use row4::minmax::{self, SearchContext};
+#[cfg(test)]
+use row4::score::Score;
+
+lazy_static! {
+    static ref STANDARD_LINES: Lines = Lines::new(BoardConfig::standard());
+    // the lines of the other configs, built the first time a board of the config is evaluated
+    static ref OTHER_LINES: RwLock<HashMap<BoardConfig, Arc<Lines>>> = RwLock::new(HashMap::new());
+}
+
+/// How many points each feature of a position is worth.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Weights {
+    // a line with all but two of its pieces and no opposing piece
+    pub open_two: i32,
+    // each empty position that would complete a line
+    pub threat: i32,
+    // extra for a threat on a row where it tends to decide the end of the game:
+    // the odd rows (counting from 1) for red, the even ones for blue
+    pub good_row_threat: i32,
+    // a game that is decided within the next two moves, as the side to move can win right away
+    // or the other side has two threats to win with that can't both be blocked
+    pub decided: i32,
+    // the difference in points that makes a win rate of about 0.73 (logistic curve)
+    pub points_scale: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights { open_two: 2, threat: 6, good_row_threat: 8, decided: 400, points_scale: 40.0 }
+    }
+}
+
+/// All lines of positions on a board that could win the game.
+struct Lines {
+    masks: Vec<u64>,
+    // the number of lines through each position, which favours the centre
+    position_weights: [i32; MAX_CELLS],
+    // the rows red likes to have its threats on
+    red_rows: u64,
+}
+
+impl Lines {
+    /// the lines of any config other than the standard one, which has its own
+    fn other(config: BoardConfig) -> Arc<Lines> {
+        if let Some(lines) = OTHER_LINES.read().unwrap().get(&config) {
+            return Arc::clone(lines);
+        }
+        Arc::clone(OTHER_LINES.write().unwrap().entry(config).or_insert_with(|| Arc::new(Lines::new(config))))
+    }
+
+    fn new(config: BoardConfig) -> Lines {
+        let mut masks = Vec::new();
+        let mut position_weights = [0; MAX_CELLS];
+        let connect = config.connect() as i8;
+        let inside = |column: i8, row: i8| column >= 0 && column < config.width() as i8 && row >= 0 && row < config.height() as i8;
+        for row in 0..config.height() as i8 {
+            for column in 0..config.width() as i8 {
+                for &(column_step, row_step) in &[(1i8, 0i8), (1, 1), (1, -1), (0, 1)] {
+                    if !inside(column + (connect - 1) * column_step, row + (connect - 1) * row_step) {
+                        continue;
+                    }
+                    let mut mask = 0;
+                    for step in 0..connect {
+                        let position = config.position_mask((column + step * column_step) as Column, (row + step * row_step) as u8);
+                        mask |= position;
+                        position_weights[position.trailing_zeros() as usize] += 1;
+                    }
+                    masks.push(mask);
+                }
+            }
+        }
+
+        let red_rows = (0..config.height()).step_by(2)
+            .flat_map(|row| (0..config.width()).map(move |column| config.position_mask(column, row)))
+            .fold(0, |rows, position| rows | position);
+        Lines { masks, position_weights, red_rows }
+    }
+
+    fn weight(&self, mut bits: u64) -> i32 {
+        let mut weight = 0;
+        while bits != 0 {
+            weight += self.position_weights[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+        weight
+    }
+
+    // the points for red, minus those for blue
+    fn points(&self, board: &Board, weights: &Weights) -> i32 {
+        let connect = board.config.connect();
+        let mut points = self.weight(board.red) - self.weight(board.blue);
+        let (mut red_threats, mut blue_threats) = (0, 0);
+        for &mask in &self.masks {
+            let (red, blue) = ((board.red & mask).count_ones() as u8, (board.blue & mask).count_ones() as u8);
+            if blue == 0 && red + 1 == connect {
+                red_threats |= mask & !board.red;
+            } else if red == 0 && blue + 1 == connect {
+                blue_threats |= mask & !board.blue;
+            } else if blue == 0 && red > 0 && red + 2 == connect {
+                points += weights.open_two;
+            } else if red == 0 && blue > 0 && blue + 2 == connect {
+                points -= weights.open_two;
+            }
+        }
+
+        let playable = board.moves.moves().iter()
+            .fold(0, |bits, &column| bits | board.config.position_mask(column, board.height(column)));
+        let (own_threats, other_threats, sign) = match board.color_to_move {
+            Color::Red => (red_threats, blue_threats, 1),
+            Color::Blue => (blue_threats, red_threats, -1)
+        };
+        if own_threats & playable != 0 {
+            return sign * weights.decided;
+        } else if (other_threats & playable).count_ones() >= 2 {
+            return -sign * weights.decided;
+        }
+
+        points += weights.threat * (red_threats.count_ones() as i32 - blue_threats.count_ones() as i32);
+        points += weights.good_row_threat * ((red_threats & self.red_rows).count_ones() as i32
+            - (blue_threats & !self.red_rows).count_ones() as i32);
+        points
+    }
+}
+
+/// A deterministic static evaluation, which plays no moves.
+///
+/// Scores open twos, threats and the rows they are on, and how central the pieces are, then
+/// maps the difference between both sides to a win rate.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Heuristic {
+    weights: Weights,
+}
+
+impl Heuristic {
+    pub fn new() -> Heuristic {
+        Heuristic::default()
+    }
+
+    pub fn with_weights(weights: Weights) -> Heuristic {
+        Heuristic { weights }
+    }
+
+    pub fn weights(&self) -> &Weights {
+        &self.weights
+    }
+}
+
+impl Evaluator for Heuristic {
+    fn evaluate(&self, board: &Board, own_color: Color) -> (f64, u64) {
+        let points = if board.config.is_standard() {
+            STANDARD_LINES.points(board, &self.weights)
+        } else {
+            Lines::other(board.config).points(board, &self.weights)
+        };
+        let red_win_rate = 1.0 / (1.0 + (-points as f64 / self.weights.points_scale).exp());
+        match own_color {
+            Color::Red => (red_win_rate, 0),
+            Color::Blue => (1.0 - red_win_rate, 0)
+        }
+    }
+}
+
+#[test]
+fn test_lines() {
+    let lines = Lines::new(BoardConfig::standard());
+    assert_eq!(lines.masks.len(), 69);
+    // the classic table of lines through each position
+    let bottom_row: Vec<i32> = (0..7).map(|column| lines.position_weights[column]).collect();
+    assert_eq!(bottom_row, vec!(3, 4, 5, 7, 5, 4, 3));
+    assert_eq!(lines.position_weights[2 * 8 + 3], 13);
+
+    assert_eq!(Lines::new(BoardConfig::new(4, 4, 4).unwrap()).masks.len(), 10);
+
+    // the lines of other configs are only built once
+    let config = BoardConfig::new(5, 4, 3).unwrap();
+    assert!(Arc::ptr_eq(&Lines::other(config), &Lines::other(config)));
+    assert_eq!(Lines::other(config).masks, Lines::new(config).masks);
+}
+
+#[test]
+fn test_evaluate() {
+    let heuristic = Heuristic::new();
+    assert_eq!(heuristic.evaluate(&Board::new(), Color::Red), (0.5, 0));
+
+    // the centre is better than the edge, for either side
+    let centre = Board::from_moves_str("4").unwrap();
+    let edge = Board::from_moves_str("1").unwrap();
+    assert!(heuristic.evaluate(&centre, Color::Red).0 > heuristic.evaluate(&edge, Color::Red).0);
+    assert_eq!(heuristic.evaluate(&centre, Color::Blue).0, 1.0 - heuristic.evaluate(&centre, Color::Red).0);
+
+    // the same result every time, and for the mirror image
+    let board = Board::from_moves_str("4453").unwrap();
+    let mirrored = Board::from_moves_str("4435").unwrap();
+    assert_eq!(heuristic.evaluate(&board, Color::Blue), heuristic.evaluate(&board, Color::Blue));
+    assert_eq!(heuristic.evaluate(&board, Color::Blue), heuristic.evaluate(&mirrored, Color::Blue));
+}
+
+#[test]
+fn test_threats() {
+    let heuristic = Heuristic::new();
+    // red threatens to complete the bottom row on both sides, or can complete it right away
+    let double_threat = Board::from_moves_str("44556").unwrap();
+    assert!(heuristic.evaluate(&double_threat, Color::Red).0 > 0.99);
+    let red_to_move = Board::from_moves_str("445561").unwrap();
+    assert!(heuristic.evaluate(&red_to_move, Color::Red).0 > 0.99);
+    // a threat on the third row, which can't be played yet
+    let config = BoardConfig::standard();
+    let bits = |positions: &[(Column, u8)]| positions.iter().fold(0, |bits, &(c, r)| bits | config.position_mask(c, r));
+    let red = bits(&[(1, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    let blue = bits(&[(0, 0), (2, 0), (1, 1)]);
+    let (win_rate, _) = heuristic.evaluate(&Board::from_bits(config, red, blue), Color::Red);
+    assert!(win_rate > 0.5 && win_rate < 0.99, "{}", win_rate);
+
+    // it counts more for red than for blue, which swapping the colors shows
+    let red_threat = STANDARD_LINES.points(&Board::from_bits(config, red, blue), heuristic.weights());
+    let blue_threat = STANDARD_LINES.points(&Board::from_bits(config, blue, red), heuristic.weights());
+    assert_eq!(red_threat + blue_threat, heuristic.weights().good_row_threat);
+}
+
+#[test]
+fn test_repeatable_search() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let never_stop = AtomicBool::new(false);
+    let search = || {
+        let cache = BoardCache::new();
+        minmax::minmax(&board, 6, &[], Score::MIN, Score::MAX, &mut SearchContext::new(Color::Red, &cache, &Heuristic::new(), &never_stop))
+    };
+    let (variant, eval, moves_played, positions) = search();
+    assert_eq!(search(), (variant, eval, moves_played, positions));
+    assert_eq!(moves_played, 0);
+    assert!(positions > 0);
+}
+
+#[test]
+fn test_weights() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let (default_eval, _) = Heuristic::new().evaluate(&board, Color::Red);
+    let flat = Heuristic::with_weights(Weights { points_scale: 1_000.0, ..Weights::default() });
+    let (flat_eval, _) = flat.evaluate(&board, Color::Red);
+    assert!((flat_eval - 0.5).abs() < (default_eval - 0.5).abs());
+}
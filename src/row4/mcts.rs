@@ -0,0 +1,284 @@
+use std::cmp::Reverse;
+use std::f64;
+
+use row4::*;
+use row4::board::{Board, GameStatus};
+use row4::monte_carlo;
+use row4::time::Timer;
+
+const EXPLORATION: f64 = f64::consts::SQRT_2;
+
+struct Node {
+    // the move leading to this node
+    column: Column,
+    // the color that played that move
+    mover: Color,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // moves that don't have a child node yet, the next one to expand last
+    untried: Vec<Column>,
+    visits: u32,
+    // wins for the mover, draws count as half a win
+    wins: f64,
+}
+
+impl Node {
+    fn new(board: &Board, column: Column, parent: Option<usize>) -> Node {
+        let mut untried = if board.status == GameStatus::Ongoing { monte_carlo::useful_moves(board) } else { vec!() };
+        untried.reverse();
+        Node { column, mover: board.color_to_move.switch(), parent, children: vec!(), untried, visits: 0, wins: 0.0 }
+    }
+
+    /// upper confidence bound used to select a child, from the view of its mover
+    fn uct(&self, parent_visits: u32) -> f64 {
+        self.wins / self.visits as f64 + EXPLORATION * ((parent_visits as f64).ln() / self.visits as f64).sqrt()
+    }
+}
+
+/// Statistics for one of the moves at the root of the search tree.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MoveStats {
+    pub column: Column,
+    pub visits: u32,
+    pub win_rate: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsResult {
+    pub best: Column,
+    // sorted by visits, most visited first
+    pub stats: Vec<MoveStats>,
+    pub playouts: u64,
+}
+
+/// Monte Carlo tree search using UCT to select moves and random playouts to evaluate them.
+///
+/// The tree is kept between searches, so that the part below the moves played in the
+/// meantime can be reused.
+pub struct Mcts {
+    board: Board,
+    // the root is always at index 0
+    nodes: Vec<Node>,
+}
+
+impl Mcts {
+    pub fn new(board: &Board) -> Mcts {
+        Mcts { board: *board, nodes: vec!(Node::new(board, 0, None)) }
+    }
+
+    /// moves the root of the tree to the given board, reusing the subtree if the board
+    /// can be reached by playing moves from the current root, starting over otherwise
+    ///
+    /// Boards without a move history, such as those read from a diagram, are only reused
+    /// if they show the same position as the current root.
+    pub fn advance_to(&mut self, board: &Board) {
+        let played = self.board.played_moves().len();
+        let reachable = board.played_moves().len() >= played
+            && board.played_moves()[..played] == *self.board.played_moves()
+            && board.config == self.board.config;
+
+        let mut root = None;
+        if reachable {
+            let mut replayed = self.board;
+            root = Some(0);
+            for &column in &board.played_moves()[played..] {
+                root = root.filter(|_| replayed.try_play_move(column).is_ok())
+                    .and_then(|index| self.nodes[index].children.iter().cloned().find(|&child| self.nodes[child].column == column));
+            }
+            let same_position = (replayed.red, replayed.blue, replayed.color_to_move) == (board.red, board.blue, board.color_to_move);
+            root = root.filter(|_| same_position);
+        }
+
+        match root {
+            Some(index) => self.reroot(index),
+            None => self.nodes = vec!(Node::new(board, 0, None))
+        }
+        self.board = *board;
+    }
+
+    /// keeps only the subtree below the given node, which becomes the new root
+    fn reroot(&mut self, index: usize) {
+        let mut old_nodes: Vec<Option<Node>> = self.nodes.drain(..).map(Some).collect();
+        let mut pending = vec!((index, None));
+        while let Some((old_index, parent)) = pending.pop() {
+            let mut node = old_nodes[old_index].take().unwrap();
+            let new_index = self.nodes.len();
+            for &child in &node.children {
+                pending.push((child, Some(new_index)));
+            }
+            node.parent = parent;
+            node.children = vec!();
+            if let Some(parent) = parent {
+                self.nodes[parent].children.push(new_index);
+            }
+            self.nodes.push(node);
+        }
+    }
+
+    /// searches for the given time and returns the most visited move at the root
+    ///
+    /// Returns None if the game is over, as there is no move to choose then.
+    pub fn search(&mut self, millis: u64) -> Option<MctsResult> {
+        if self.board.status != GameStatus::Ongoing {
+            return None;
+        }
+        let timer = Timer::new();
+        let mut playouts = 0;
+        while playouts == 0 || timer.elapsed_millis() < millis {
+            self.iterate();
+            playouts += 1;
+        }
+        self.result(playouts)
+    }
+
+    /// runs the given number of iterations and returns the most visited move at the root
+    ///
+    /// Returns None if no move at the root has been tried yet, such as when the game is over.
+    pub fn search_iterations(&mut self, iterations: u64) -> Option<MctsResult> {
+        for _ in 0..iterations {
+            self.iterate();
+        }
+        self.result(iterations)
+    }
+
+    fn result(&self, playouts: u64) -> Option<MctsResult> {
+        let mut stats: Vec<MoveStats> = self.nodes[0].children.iter().map(|&child| {
+            let node = &self.nodes[child];
+            MoveStats { column: node.column, visits: node.visits, win_rate: node.wins / node.visits as f64 }
+        }).collect();
+        stats.sort_by_key(|stats| Reverse(stats.visits));
+
+        let best = stats.first()?.column;
+        Some(MctsResult { best, stats, playouts })
+    }
+
+    /// one round of selection, expansion, simulation and backpropagation
+    fn iterate(&mut self) {
+        let mut board = self.board;
+        let mut index = 0;
+
+        // selection
+        while self.nodes[index].untried.is_empty() && !self.nodes[index].children.is_empty() {
+            let parent_visits = self.nodes[index].visits;
+            let mut best_child = self.nodes[index].children[0];
+            let mut best_uct = f64::NEG_INFINITY;
+            for &child in &self.nodes[index].children {
+                let uct = self.nodes[child].uct(parent_visits);
+                if uct > best_uct {
+                    best_uct = uct;
+                    best_child = child;
+                }
+            }
+            index = best_child;
+            board.play_move(self.nodes[index].column);
+        }
+
+        // expansion
+        if let Some(column) = self.nodes[index].untried.pop() {
+            board.play_move(column);
+            let child = self.nodes.len();
+            self.nodes.push(Node::new(&board, column, Some(index)));
+            self.nodes[index].children.push(child);
+            index = child;
+        }
+
+        // simulation
+        let (_, status) = monte_carlo::play_random_game(&mut board);
+
+        // backpropagation
+        let mut current = Some(index);
+        while let Some(node_index) = current {
+            let node = &mut self.nodes[node_index];
+            node.visits += 1;
+            node.wins += match status {
+                GameStatus::Won(color) if color == node.mover => 1.0,
+                GameStatus::Draw => 0.5,
+                _ => 0.0
+            };
+            current = node.parent;
+        }
+    }
+}
+
+#[test]
+fn test_finds_win() {
+    let board = Board::from_moves_str("445566").unwrap();
+    let mut mcts = Mcts::new(&board);
+    let result = mcts.search_iterations(200).unwrap();
+    assert_eq!(result.best, 2);
+    assert_eq!(result.stats, vec!(MoveStats { column: 2, visits: 200, win_rate: 1.0 }));
+}
+
+#[test]
+fn test_lost_position() {
+    // red threatens to win on both sides, blue can only block one of them
+    let board = Board::from_moves_str("44556").unwrap();
+    let mut mcts = Mcts::new(&board);
+    let result = mcts.search_iterations(500).unwrap();
+    assert_eq!(result.best, 2);
+    assert_eq!(result.stats[0].visits, 500);
+    assert_eq!(result.stats[0].win_rate, 0.0);
+    assert_eq!(result.playouts, 500);
+}
+
+#[test]
+fn test_stats() {
+    let board = Board::new();
+    let mut mcts = Mcts::new(&board);
+    let result = mcts.search_iterations(2_000).unwrap();
+    assert_eq!(result.stats.len(), 7);
+    assert_eq!(result.stats.iter().map(|stats| stats.visits as u64).sum::<u64>(), 2_000);
+    assert_eq!(result.best, result.stats[0].column);
+    assert!(result.stats.windows(2).all(|pair| pair[0].visits >= pair[1].visits));
+    assert!(result.stats.iter().all(|stats| stats.win_rate > 0.0 && stats.win_rate < 1.0));
+}
+
+#[test]
+fn test_finished_game() {
+    // red has won, so there is nothing left to search
+    let board = Board::from_moves_str("4455663").unwrap();
+    let mut mcts = Mcts::new(&board);
+    assert_eq!(mcts.search(10), None);
+    assert_eq!(mcts.search_iterations(100), None);
+
+    // nor is there a result before the first iteration
+    let mut mcts = Mcts::new(&Board::new());
+    assert_eq!(mcts.search_iterations(0), None);
+    assert!(mcts.search_iterations(1).is_some());
+}
+
+#[test]
+fn test_tree_reuse() {
+    let mut board = Board::new();
+    let mut mcts = Mcts::new(&board);
+    let result = mcts.search_iterations(1_000).unwrap();
+
+    board.play_moves(&[result.best, 3]);
+    let expected_visits = {
+        let child = mcts.nodes[0].children.iter().cloned().find(|&child| mcts.nodes[child].column == result.best).unwrap();
+        let grandchild = mcts.nodes[child].children.iter().cloned().find(|&index| mcts.nodes[index].column == 3).unwrap();
+        mcts.nodes[grandchild].visits
+    };
+    mcts.advance_to(&board);
+    assert_eq!(mcts.nodes[0].visits, expected_visits);
+    assert_eq!(mcts.nodes[0].parent, None);
+    assert!(mcts.nodes.iter().skip(1).all(|node| node.parent.is_some()));
+
+    mcts.advance_to(&Board::from_moves_str("1").unwrap());
+    assert_eq!(mcts.nodes.len(), 1);
+    // a diagram has no moves to follow, so its position has to match
+    let diagram = format!("{}", Board::from_moves_str("444444").unwrap());
+    mcts.advance_to(&Board::new());
+    mcts.search_iterations(1_000);
+    mcts.advance_to(&Board::from_diagram(&diagram).unwrap());
+    assert_eq!(mcts.nodes.len(), 1);
+    let result = mcts.search_iterations(100).unwrap();
+    assert!(result.stats.iter().all(|stats| stats.column != 3));
+
+    // while the same position is kept
+    let empty = format!("{}", Board::new());
+    mcts.advance_to(&Board::new());
+    mcts.search_iterations(100);
+    mcts.advance_to(&Board::from_diagram(&empty).unwrap());
+    assert_eq!(mcts.nodes[0].visits, 100);
+}
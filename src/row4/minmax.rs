@@ -1,80 +1,464 @@
-use std::f64;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
 
 use row4::*;
-use row4::board::Board;
+use row4::board::{Board, GameStatus};
 use row4::cache::BoardCache;
+use row4::evaluator::Evaluator;
+use row4::score::Score;
+use row4::ordering::MoveOrdering;
+use row4::transposition::{Bound, TableEntry};
 use row4::time::Timer;
 
-type Evaluate = fn(&Board, Color, u32) -> (f64, u64);
+// plies a single line may be extended by, so that long forcing sequences don't blow up the search
+const MAX_EXTENSIONS: u8 = 8;
 
-const GAMES_PER_EVALUATION: u32 = 80;
+// half the width of an aspiration window, as a raw score (a win rate of 0.05)
+const ASPIRATION_WINDOW: i32 = 50_000;
 
-pub fn iterative_minmax(board: &Board, own_color: Color, millis: u64, cache: &mut BoardCache, evaluate: Evaluate) -> (Vec<Column>, f64, u64, u64) {
+/// How a search is run, to compare the node counts with and without each technique.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SearchOptions {
+    // threads to split up the root moves between
+    pub num_threads: usize,
+    // searches all but the first move of a node with a null window, searching again if it fails
+    pub principal_variation: bool,
+    // starts each iteration with a narrow window around the eval of the previous one
+    pub aspiration_windows: bool,
+    // plies a line may be extended by for forced moves and threats, none disables extensions
+    pub max_extensions: u8,
+    // root moves to rank with variants and evals of their own, searched without aspiration
+    // windows if more than 1
+    pub multi_pv: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions { num_threads: 1, principal_variation: true, aspiration_windows: true, max_extensions: MAX_EXTENSIONS, multi_pv: 1 }
+    }
+}
+
+impl SearchOptions {
+    /// plain alpha-beta on a single thread, searching each iteration with a full window and without extensions
+    pub fn plain() -> SearchOptions {
+        SearchOptions { num_threads: 1, principal_variation: false, aspiration_windows: false, max_extensions: 0, multi_pv: 1 }
+    }
+
+    /// the same options with at least one thread and ranked move, and without aspiration windows
+    /// for MultiPV, where each root move has a window of its own
+    pub fn normalized(self) -> SearchOptions {
+        let multi_pv = usize::max(1, self.multi_pv);
+        SearchOptions {
+            num_threads: usize::max(1, self.num_threads),
+            aspiration_windows: self.aspiration_windows && multi_pv == 1,
+            multi_pv,
+            ..self
+        }
+    }
+}
+
+/// Progress of an iterative search, reported after each completed depth.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub elapsed_micros: u64,
+    // moves played in simulations so far
+    pub moves_played: u64,
+    // positions evaluated so far
+    pub positions: u64,
+    // positions evaluated for this depth alone
+    pub depth_positions: u64,
+    // the number of pieces on the searched board
+    pub num_pieces: u8,
+    pub eval: Score,
+    // the main variant in playing order
+    pub variant: Vec<Column>,
+    // the best root moves, best first, with the main variant only unless more are asked for
+    pub ranked: Vec<RankedVariant>,
+}
+
+/// A root move with its own variant and eval, as ranked by a MultiPV search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankedVariant {
+    // in playing order, starting with the root move
+    pub variant: Vec<Column>,
+    pub eval: Score,
+}
+
+impl SearchInfo {
+    pub fn moves_per_second(&self) -> u64 {
+        (self.moves_played * 1_000_000).checked_div(self.elapsed_micros).unwrap_or(0)
+    }
+
+    pub fn positions_per_second(&self) -> u64 {
+        (self.positions * 1_000_000).checked_div(self.elapsed_micros).unwrap_or(0)
+    }
+}
+
+/// Receives the progress of a search.
+pub trait SearchObserver: Send {
+    fn on_iteration(&mut self, info: &SearchInfo);
+}
+
+/// Prints the progress of a search to stdout.
+pub struct PrintObserver;
+
+impl SearchObserver for PrintObserver {
+    fn on_iteration(&mut self, info: &SearchInfo) {
+        println!("depth: {}, elapsed: {} ms, moves: {} ({} moves/s), positions: {} ({} at this depth, {} positions/s), eval: {}, variant: {:?}",
+                 info.depth, info.elapsed_micros / 1_000, info.moves_played, info.moves_per_second(), info.positions,
+                 info.depth_positions, info.positions_per_second(), info.eval.relative_to(info.num_pieces), info.variant);
+        if info.ranked.len() > 1 {
+            for (rank, ranked) in info.ranked.iter().enumerate() {
+                println!("  {}. eval: {}, variant: {:?}", rank + 1, ranked.eval.relative_to(info.num_pieces), ranked.variant);
+            }
+        }
+    }
+}
+
+/// Ignores the progress of a search.
+pub struct SilentObserver;
+
+impl SearchObserver for SilentObserver {
+    fn on_iteration(&mut self, _info: &SearchInfo) {}
+}
+
+/// Sends the progress of a search through a channel, ignoring a closed receiver.
+impl SearchObserver for Sender<SearchInfo> {
+    fn on_iteration(&mut self, info: &SearchInfo) {
+        let _ = self.send(info.clone());
+    }
+}
+
+/// Everything a search needs besides the position, passed down through `minmax`.
+///
+/// The move ordering is updated during the search, so each thread needs its own context.
+pub struct SearchContext<'a, E: 'a + Evaluator + ?Sized> {
+    // the side the scores are given for
+    pub own_color: Color,
+    pub cache: &'a BoardCache,
+    pub evaluator: &'a E,
+    pub stop: &'a AtomicBool,
+    pub ordering: MoveOrdering,
+    pub options: SearchOptions,
+    // plies the current line has been extended by
+    pub extensions: u8,
+}
+
+// derived, it would only be cloneable for cloneable evaluators
+impl<'a, E: Evaluator + ?Sized> Clone for SearchContext<'a, E> {
+    fn clone(&self) -> SearchContext<'a, E> {
+        SearchContext { ordering: self.ordering.clone(), ..*self }
+    }
+}
+
+impl<'a, E: Evaluator + ?Sized> SearchContext<'a, E> {
+    pub fn new(own_color: Color, cache: &'a BoardCache, evaluator: &'a E, stop: &'a AtomicBool) -> SearchContext<'a, E> {
+        SearchContext { own_color, cache, evaluator, stop, ordering: MoveOrdering::new(), options: SearchOptions::default(), extensions: 0 }
+    }
+
+    fn can_extend(&self) -> bool {
+        self.extensions < self.options.max_extensions
+    }
+
+    // 1 if the move to the given position doesn't reduce the depth, as it is forced or leaves the opponent
+    // a single move only, which means it either threatens to win or blunders the game
+    fn extension(&self, forced: bool, sim: &Board) -> u8 {
+        let threat = || sim.status == GameStatus::Ongoing && monte_carlo::useful_moves(sim).len() == 1;
+        (self.can_extend() && (forced || threat())) as u8
+    }
+}
+
+/// searches deeper and deeper until the time is up or the stop flag is set
+///
+/// An iteration that gets stopped is discarded, except for the first one, which always
+/// completes so that there is a move to play.
+///
+/// With more than one thread, the moves at the root are split up between the threads.
+/// With aspiration windows, an iteration whose eval falls outside of the window around the
+/// previous eval is searched again with the window opened up on that side. With MultiPV,
+/// the search goes on until all ranked moves are won or lost.
+#[allow(clippy::too_many_arguments)]
+pub fn iterative_minmax<E: Evaluator + ?Sized>(board: &Board, own_color: Color, millis: u64, cache: &BoardCache, evaluator: &E,
+                                               observer: &mut dyn SearchObserver, stop: &AtomicBool, options: SearchOptions) -> (Vec<Column>, Score, u64, u64) {
     let mut depth = 0;
     let mut main_variant = vec!();
     let mut elapsed = 0;
-    let mut current_eval = 0.0;
+    let mut current_eval = Score::estimate(0.5);
+    let mut current_ranked: Vec<(Vec<Column>, Score)> = vec!();
+    // the eval of the iteration before the current one
+    let mut previous_eval: Option<Score> = None;
     let mut current_moves_played = 0;
     let mut current_positions = 0;
 
     let timer = Timer::new();
+    let never_stop = AtomicBool::new(false);
+    let num_empty = board.config.size() - board.num_pieces();
+    cache.table().new_search();
+    let mut ordering = MoveOrdering::new();
 
-    while elapsed < millis * 1_000 && main_variant.len() >= depth as usize {
+    // there's no need to search deeper once the results are won or lost, or the board is full
+    //
+    // A draw isn't final before the board is full: it competes with estimates, which may
+    // turn out to be wins or losses further down.
+    let decided = |eval: Score| eval.is_win() || eval.is_loss();
+    while depth == 0 || (elapsed < millis * 1_000 && !current_ranked.iter().all(|&(_, eval)| decided(eval)) && depth < num_empty
+            && !stop.load(Ordering::Relaxed)) {
         depth += 1;
-        let (updated_main_variant, eval, moves_played, positions) =
-            minmax(board, own_color, depth, &main_variant, -1.0, 2.0, cache, evaluate);
+        let iteration_stop = if depth == 1 { &never_stop } else { stop };
+        let mut context = SearchContext { ordering, options, ..SearchContext::new(own_color, cache, evaluator, iteration_stop) };
+        let (ranked, moves_played, positions) = if options.multi_pv > 1 {
+            multi_pv_minmax(board, depth, &main_variant, options.multi_pv, &mut context)
+        } else {
+            let (variant, eval, moves_played, positions) = aspiration_minmax(board, depth, &main_variant, previous_eval, &mut context);
+            (vec!((variant, eval)), moves_played, positions)
+        };
+        ordering = context.ordering;
+        if iteration_stop.load(Ordering::Relaxed) {
+            break;
+        }
         elapsed = timer.elapsed_micros();
-        main_variant = updated_main_variant;
+        let eval = ranked[0].1;
+        main_variant = ranked[0].0.clone();
+        current_ranked = ranked;
+        previous_eval = Some(current_eval).filter(|_| depth > 1);
         current_eval = eval;
         current_moves_played += moves_played;
         current_positions += positions;
 
-        let (moves_per_second, positions_per_second) = if elapsed != 0 {
-            ((current_moves_played * 1_000_000) / elapsed, (current_positions * 1_000_000) / elapsed)
-        } else {
-            (0, 0)
-        };
-        println!("depth: {}, elapsed: {} ms, moves: {} ({} moves/s), positions: {} ({} positions/s), eval: {}, variant: {:?}",
-                 depth, elapsed, current_moves_played, moves_per_second, current_positions, positions_per_second, eval, main_variant);
+        observer.on_iteration(&SearchInfo {
+            depth,
+            elapsed_micros: elapsed,
+            moves_played: current_moves_played,
+            positions: current_positions,
+            depth_positions: positions,
+            num_pieces: board.num_pieces(),
+            eval,
+            variant: main_variant.iter().rev().cloned().collect(),
+            ranked: current_ranked.iter()
+                .map(|&(ref variant, eval)| RankedVariant { variant: variant.iter().rev().cloned().collect(), eval })
+                .collect(),
+        });
     }
 
     (main_variant, current_eval, current_moves_played, current_positions)
 }
 
-pub fn minmax(board: &Board, own_color: Color, depth: u8, main_variant: &[Column], mut alpha: f64, mut beta: f64, cache: &mut BoardCache, evaluate: Evaluate) -> (Vec<Column>, f64, u64, u64) {
-    match board.winner {
-        Some(color) if color == own_color => return (Vec::new(), 1.0, 0, 1),
-        Some(_) => return (Vec::new(), 0.0, 0, 1),
-        None => if board.moves.len() == 0 {
-            return (Vec::new(), 0.5, 0, 1);
+// keeps the ranking of the last completed depth before passing the progress on
+struct LastRanking<'a> {
+    ranked: Vec<RankedVariant>,
+    observer: &'a mut dyn SearchObserver,
+}
+
+impl<'a> SearchObserver for LastRanking<'a> {
+    fn on_iteration(&mut self, info: &SearchInfo) {
+        self.ranked = info.ranked.clone();
+        self.observer.on_iteration(info);
+    }
+}
+
+/// ranks the best moves for the side to move, searching like `iterative_minmax` for the given time
+///
+/// The number of moves comes from `multi_pv` in the options. The ranking of the last completed
+/// depth is returned, best first.
+pub fn analyze<E: Evaluator + ?Sized>(board: &Board, millis: u64, cache: &BoardCache, evaluator: &E,
+                                      observer: &mut dyn SearchObserver, options: SearchOptions) -> Vec<RankedVariant> {
+    let mut last_ranking = LastRanking { ranked: vec!(), observer };
+    iterative_minmax(board, board.color_to_move, millis, cache, evaluator, &mut last_ranking, &AtomicBool::new(false), options.normalized());
+    last_ranking.ranked
+}
+
+// searches the root like `minmax`, in a window around the given previous eval if aspiration windows
+// are enabled, opening it up on the side the eval falls outside of
+//
+// Evals swing between odd and even depths, so the previous eval is the one from two depths before.
+fn aspiration_minmax<E: Evaluator + ?Sized>(board: &Board, depth: u8, main_variant: &[Column], previous_eval: Option<Score>,
+                                            context: &mut SearchContext<E>) -> (Vec<Column>, Score, u64, u64) {
+    let (mut alpha, mut beta) = match previous_eval {
+        Some(previous_eval) if context.options.aspiration_windows =>
+            (previous_eval.shifted(-ASPIRATION_WINDOW), previous_eval.shifted(ASPIRATION_WINDOW)),
+        _ => (Score::MIN, Score::MAX)
+    };
+    let (mut moves_played, mut positions) = (0, 0);
+    loop {
+        let (variant, eval, window_moves_played, window_positions) = if context.options.num_threads > 1 {
+            parallel_minmax(board, depth, main_variant, alpha, beta, context)
+        } else {
+            minmax(board, depth, main_variant, alpha, beta, context)
+        };
+        moves_played += window_moves_played;
+        positions += window_positions;
+        if eval <= alpha && alpha != Score::MIN {
+            alpha = Score::MIN;
+        } else if eval >= beta && beta != Score::MAX {
+            beta = Score::MAX;
+        } else {
+            return (variant, eval, moves_played, positions);
         }
+    }
+}
+
+/// The outcome of a search that ran in the background.
+pub struct FinishedSearch {
+    // the main variant, with the first move last
+    pub variant: Vec<Column>,
+    pub eval: Score,
+    pub moves_played: u64,
+    pub positions: u64,
+    // the cache and observer that were handed to the search
+    pub cache: BoardCache,
+    pub observer: Box<dyn SearchObserver>,
+}
+
+/// Handle to an iterative minmax search running on its own thread.
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    best_so_far: Arc<Mutex<Option<SearchInfo>>>,
+    // only taken when joining
+    thread: Option<JoinHandle<FinishedSearch>>,
+}
+
+// keeps the latest progress for the handle before passing it on
+struct LatestInfo {
+    latest: Arc<Mutex<Option<SearchInfo>>>,
+    observer: Box<dyn SearchObserver>,
+}
+
+impl SearchObserver for LatestInfo {
+    fn on_iteration(&mut self, info: &SearchInfo) {
+        *self.latest.lock().unwrap() = Some(info.clone());
+        self.observer.on_iteration(info);
+    }
+}
+
+impl SearchHandle {
+    /// starts `iterative_minmax` on a new thread, which owns the cache until the search is joined
+    pub fn spawn<E: Evaluator + ?Sized + 'static>(board: &Board, own_color: Color, millis: u64, cache: BoardCache, evaluator: Arc<E>,
+                                                  observer: Box<dyn SearchObserver>, options: SearchOptions) -> SearchHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let best_so_far = Arc::new(Mutex::new(None));
+        let board = *board;
+        let thread_stop = Arc::clone(&stop);
+        let mut thread_observer = LatestInfo { latest: Arc::clone(&best_so_far), observer };
+
+        let thread = thread::spawn(move || {
+            let (variant, eval, moves_played, positions) =
+                iterative_minmax(&board, own_color, millis, &cache, &*evaluator, &mut thread_observer, &thread_stop, options);
+            FinishedSearch { variant, eval, moves_played, positions, cache, observer: thread_observer.observer }
+        });
+
+        SearchHandle { stop, best_so_far, thread: Some(thread) }
+    }
+
+    /// asks the search to finish as soon as possible, keeping the last completed depth
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// the progress after the last completed depth, if there is one yet
+    pub fn best_so_far(&self) -> Option<SearchInfo> {
+        self.best_so_far.lock().unwrap().clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|thread| thread.is_finished())
+    }
+
+    /// waits for the search to finish
+    pub fn join(mut self) -> FinishedSearch {
+        self.thread.take().unwrap().join().unwrap()
+    }
+}
+
+/// A search that is dropped without joining it gets stopped.
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+pub fn minmax<E: Evaluator + ?Sized>(board: &Board, depth: u8, main_variant: &[Column], mut alpha: Score, mut beta: Score,
+                                     context: &mut SearchContext<E>) -> (Vec<Column>, Score, u64, u64) {
+    // the result doesn't matter any more, it gets discarded
+    if context.stop.load(Ordering::Relaxed) {
+        return (Vec::new(), Score::STOPPED, 0, 0);
+    }
+
+    let own_color = context.own_color;
+    match board.status {
+        GameStatus::Won(color) if color == own_color => return (Vec::new(), Score::win_at(board.num_pieces()), 0, 1),
+        GameStatus::Won(_) => return (Vec::new(), Score::loss_at(board.num_pieces()), 0, 1),
+        GameStatus::Draw => return (Vec::new(), Score::DRAW, 0, 1),
+        GameStatus::Ongoing => ()
     };
 
-    if depth == 0 {
-        let (result, moves_played) = match cache.get(board) {
-            Some(&eval) => (eval, 0),
+    // previous results are used unless the node is part of the main variant, which is searched in full
+    let (original_alpha, original_beta) = (alpha, beta);
+    let entry = context.cache.table().get(board, own_color);
+    if let Some(entry) = entry {
+        let cutoff = match entry.bound {
+            Bound::Exact => true,
+            Bound::Lower => entry.score >= beta,
+            Bound::Upper => entry.score <= alpha
+        };
+        if cutoff && entry.depth >= depth && main_variant.is_empty() {
+            return (entry.best_move.into_iter().collect(), entry.score, 0, 1);
+        }
+    }
+
+    // a forced move is played instead of evaluating the position, to read forcing sequences to their end
+    let mut moves = monte_carlo::useful_moves(board);
+    let forced = moves.len() == 1;
+    if depth == 0 && !(forced && context.can_extend()) {
+        let (result, moves_played) = match context.cache.get(board, own_color) {
+            Some(eval) => (eval, 0),
             None => {
-                let (eval, moves_played) = evaluate(board, own_color, GAMES_PER_EVALUATION);
-                cache.store(board, eval, true);
+                let (eval, moves_played) = context.evaluator.evaluate(board, own_color);
+                context.cache.store(board, own_color, eval);
                 (eval, moves_played)
             }
         };
-        return (Vec::new(), result, moves_played, 1);
+        return (Vec::new(), Score::estimate(result), moves_played, 1);
     }
 
     let maximize = own_color == board.color_to_move;
     let mut best_variant = Vec::new();
-    let mut best_eval = if maximize { -1.0 } else { 2.0 };
+    let mut best_eval = if maximize { Score::MIN } else { Score::MAX };
     let mut num_moves = 0;
     let mut num_positions = 0;
 
-    let mut moves = monte_carlo::useful_moves(board);
+    // the main variant comes first, then the best move found before, then killers and history
+    context.ordering.order(board, &mut moves);
+    if let Some(best_move) = entry.and_then(|entry| entry.best_move).filter(|column| moves.contains(column)) {
+        put_main_variant_first(&mut moves, &[best_move]);
+    }
     let mut updated_main_variant = put_main_variant_first(&mut moves, main_variant);
 
-    for column in moves {
-        let mut sim = board.clone();
-        sim.play_move(column, true);
-        let (mut variant, eval, moves, positions) = minmax(&sim, own_color, depth - 1, &updated_main_variant,alpha, beta, cache, evaluate);
+    for (index, column) in moves.into_iter().enumerate() {
+        let mut sim = *board;
+        sim.play_move(column);
+        let extension = context.extension(forced, &sim);
+        let child_depth = depth + extension - 1;
+        context.extensions += extension;
+        let null_window = context.options.principal_variation && index > 0 && depth > 1;
+        let mut result = if null_window {
+            // only shows whether the move is better than the best one so far
+            let (null_alpha, null_beta) = if maximize { (alpha, alpha.shifted(1)) } else { (beta.shifted(-1), beta) };
+            minmax(&sim, child_depth, &[], null_alpha, null_beta, context)
+        } else {
+            minmax(&sim, child_depth, &updated_main_variant, alpha, beta, context)
+        };
+        if null_window && alpha < result.1 && result.1 < beta {
+            // it is better, so its exact eval is needed
+            let (moves, positions) = (result.2, result.3);
+            result = minmax(&sim, child_depth, &[], alpha, beta, context);
+            result.2 += moves;
+            result.3 += positions;
+        }
+        context.extensions -= extension;
+        let (mut variant, eval, moves, positions) = result;
         updated_main_variant = vec!();
         variant.push(column);
 
@@ -86,25 +470,174 @@ pub fn minmax(board: &Board, own_color: Color, depth: u8, main_variant: &[Column
                 best_eval = eval;
                 best_variant = variant;
             }
-            alpha = f64::max(alpha, eval);
-            if beta <= alpha {
-                break; // beta cut-off
-            }
+            alpha = Score::max(alpha, eval);
         } else {
             if eval < best_eval {
                 best_eval = eval;
                 best_variant = variant;
             }
-            beta = f64::min(beta, eval);
-            if beta <= alpha {
-                break; // alpha cut-off
-            }
+            beta = Score::min(beta, eval);
         }
+        if beta <= alpha {
+            context.ordering.record_cutoff(board, column, depth);
+            break; // alpha or beta cut-off
+        }
+    }
+
+    if !context.stop.load(Ordering::Relaxed) {
+        let bound = if best_eval <= original_alpha {
+            Bound::Upper
+        } else if best_eval >= original_beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        context.cache.table().store(board, own_color, TableEntry { score: best_eval, depth, bound, best_move: best_variant.last().cloned() });
     }
 
     (best_variant, best_eval, num_moves, num_positions)
 }
 
+/// searches like `minmax`, splitting up the moves at the root between the threads of the options
+///
+/// The main variant move is searched first on its own, so that the other threads start out
+/// with its evaluation as a bound. The threads share the cache and the best evaluation so far,
+/// each with its own copy of the move ordering.
+pub fn parallel_minmax<E: Evaluator + ?Sized>(board: &Board, depth: u8, main_variant: &[Column], alpha: Score, beta: Score,
+                                              context: &mut SearchContext<E>) -> (Vec<Column>, Score, u64, u64) {
+    if board.status != GameStatus::Ongoing || depth == 0 {
+        return minmax(board, depth, main_variant, alpha, beta, context);
+    }
+
+    let maximize = context.own_color == board.color_to_move;
+    let mut moves = monte_carlo::useful_moves(board);
+    let forced = moves.len() == 1;
+    context.ordering.order(board, &mut moves);
+    let updated_main_variant = put_main_variant_first(&mut moves, main_variant);
+
+    let search_move = |column: Column, main_variant: &[Column], best_eval: Score, context: &mut SearchContext<E>| {
+        let (alpha, beta) = if maximize { (Score::max(alpha, best_eval), beta) } else { (alpha, Score::min(beta, best_eval)) };
+        let mut sim = *board;
+        sim.play_move(column);
+        let extension = context.extension(forced, &sim);
+        context.extensions += extension;
+        let (mut variant, eval, num_moves, num_positions) = minmax(&sim, depth + extension - 1, main_variant, alpha, beta, context);
+        context.extensions -= extension;
+        variant.push(column);
+        (variant, eval, num_moves, num_positions)
+    };
+
+    let (best_variant, best_eval, num_moves, num_positions) =
+        search_move(moves[0], &updated_main_variant, if maximize { Score::MIN } else { Score::MAX }, context);
+    let best = Mutex::new((best_variant, best_eval));
+    let counts = Mutex::new((num_moves, num_positions));
+    let next_move = AtomicUsize::new(1);
+    let cutoff = |eval: Score| if maximize { eval >= beta } else { eval <= alpha };
+
+    thread::scope(|scope| {
+        for _ in 0..context.options.num_threads {
+            let mut thread_context = context.clone();
+            let (best, counts, next_move, moves, search_move, cutoff) = (&best, &counts, &next_move, &moves, &search_move, &cutoff);
+            scope.spawn(move || {
+                loop {
+                    let index = next_move.fetch_add(1, Ordering::Relaxed);
+                    let best_eval = best.lock().unwrap().1;
+                    if index >= moves.len() || cutoff(best_eval) {
+                        break;
+                    }
+                    let (variant, eval, num_moves, num_positions) = search_move(moves[index], &[], best_eval, &mut thread_context);
+
+                    let mut counts = counts.lock().unwrap();
+                    counts.0 += num_moves;
+                    counts.1 += num_positions;
+                    let mut best = best.lock().unwrap();
+                    if (maximize && eval > best.1) || (!maximize && eval < best.1) {
+                        *best = (variant, eval);
+                    }
+                }
+            });
+        }
+    });
+
+    let (best_variant, best_eval) = best.into_inner().unwrap();
+    let (num_moves, num_positions) = counts.into_inner().unwrap();
+    (best_variant, best_eval, num_moves, num_positions)
+}
+
+/// searches every move at the root, keeping the evals of the given number of best moves exact
+///
+/// A move is only cut off once it can't make it into the ranking any more, so the alternatives
+/// get variants and evals of their own instead of bounds. All legal moves are ranked, even when
+/// a win has to be taken or a loss blocked. The ranking comes best first, with the variants having
+/// their first move last as in `minmax`. With more than one thread in the options, the threads take
+/// turns picking the next move at the root, so the order of equal evals may vary.
+pub fn multi_pv_minmax<E: Evaluator + ?Sized>(board: &Board, depth: u8, main_variant: &[Column], num_variants: usize,
+                                              context: &mut SearchContext<E>) -> (Vec<(Vec<Column>, Score)>, u64, u64) {
+    if board.status != GameStatus::Ongoing || depth == 0 {
+        let (variant, eval, num_moves, num_positions) = minmax(board, depth, main_variant, Score::MIN, Score::MAX, context);
+        return (vec!((variant, eval)), num_moves, num_positions);
+    }
+
+    let num_variants = usize::max(1, num_variants);
+    let maximize = context.own_color == board.color_to_move;
+    let better = |eval: Score, other: Score| if maximize { eval > other } else { eval < other };
+    let mut moves = board.moves.moves();
+    let forced = moves.len() == 1;
+    context.ordering.order(board, &mut moves);
+    let updated_main_variant = put_main_variant_first(&mut moves, main_variant);
+
+    let ranked: Mutex<Vec<(Vec<Column>, Score)>> = Mutex::new(Vec::new());
+    let counts = Mutex::new((0, 0));
+    let next_move = AtomicUsize::new(0);
+    let search_moves = |context: &mut SearchContext<E>| {
+        loop {
+            let index = next_move.fetch_add(1, Ordering::Relaxed);
+            if index >= moves.len() {
+                break;
+            }
+            // the window only excludes evals that are no better than the last ranked one
+            let (alpha, beta) = {
+                let ranked = ranked.lock().unwrap();
+                match ranked.last() {
+                    Some(&(_, worst)) if ranked.len() >= num_variants => if maximize { (worst, Score::MAX) } else { (Score::MIN, worst) },
+                    _ => (Score::MIN, Score::MAX)
+                }
+            };
+            let main_variant = if index == 0 { &updated_main_variant[..] } else { &[] };
+            let mut sim = *board;
+            sim.play_move(moves[index]);
+            let extension = context.extension(forced, &sim);
+            context.extensions += extension;
+            let (mut variant, eval, num_moves, num_positions) = minmax(&sim, depth + extension - 1, main_variant, alpha, beta, context);
+            context.extensions -= extension;
+            variant.push(moves[index]);
+
+            let mut counts = counts.lock().unwrap();
+            counts.0 += num_moves;
+            counts.1 += num_positions;
+            let mut ranked = ranked.lock().unwrap();
+            let rank = ranked.iter().position(|&(_, other)| better(eval, other)).unwrap_or(ranked.len());
+            ranked.insert(rank, (variant, eval));
+            ranked.truncate(num_variants);
+        }
+    };
+
+    if context.options.num_threads > 1 {
+        thread::scope(|scope| {
+            for _ in 0..context.options.num_threads {
+                let mut thread_context = context.clone();
+                let search_moves = &search_moves;
+                scope.spawn(move || search_moves(&mut thread_context));
+            }
+        });
+    } else {
+        search_moves(context);
+    }
+
+    let (num_moves, num_positions) = counts.into_inner().unwrap();
+    (ranked.into_inner().unwrap(), num_moves, num_positions)
+}
+
 fn put_main_variant_first(moves: &mut Vec<Column>, main_variant: &[Column]) -> Vec<Column> {
     let mut updated_main_variant = main_variant.to_vec();
     match updated_main_variant.pop() {
@@ -145,3 +678,394 @@ fn test_put_main_variant_first_for_disjunct_main_variant() { // should never rea
     assert_eq!(put_main_variant_first(&mut moves, &[1, 3, 3, 2, 1, 5]), vec!(1, 3, 3, 2, 1));
     assert_eq!(moves, vec!(5, 3, 4, 2, 1));
 }
+
+#[test]
+fn test_iterative_minmax_reports_progress() {
+    use std::sync::mpsc;
+
+    let board = Board::from_moves_str("4455").unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let mut observer = sender;
+    let (variant, eval, _, positions) =
+        iterative_minmax(&board, Color::Red, 20, &BoardCache::new(), &monte_carlo::MonteCarlo::new(), &mut observer, &AtomicBool::new(false), SearchOptions::default());
+    drop(observer);
+
+    let infos: Vec<SearchInfo> = receiver.iter().collect();
+    assert!(!infos.is_empty());
+    assert!(infos.iter().enumerate().all(|(index, info)| info.depth as usize == index + 1));
+    let last = infos.last().unwrap();
+    assert_eq!(last.eval, eval);
+    assert_eq!(last.positions, positions);
+    assert_eq!(last.variant, variant.iter().rev().cloned().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_search_handle_stop() {
+    let board = Board::new();
+    let handle = SearchHandle::spawn(&board, Color::Red, 60_000, BoardCache::new(), Arc::new(monte_carlo::MonteCarlo::new()), Box::new(SilentObserver),
+                                    SearchOptions { num_threads: 2, ..SearchOptions::default() });
+    while handle.best_so_far().is_none() {
+        thread::yield_now();
+    }
+
+    let timer = Timer::new();
+    handle.stop();
+    let finished = handle.join();
+    assert!(timer.elapsed_millis() < 5_000);
+    assert!(!finished.variant.is_empty());
+    assert!(finished.positions > 0);
+}
+
+#[test]
+fn test_search_handle_join() {
+    let board = Board::from_moves_str("445566").unwrap();
+    let handle = SearchHandle::spawn(&board, Color::Red, 10, BoardCache::new(), Arc::new(monte_carlo::MonteCarlo::new()), Box::new(SilentObserver),
+                                    SearchOptions { num_threads: 2, ..SearchOptions::default() });
+    let finished = handle.join();
+    assert_eq!(finished.variant.last(), Some(&2));
+    assert_eq!(finished.eval, Score::win_at(7));
+}
+
+// a deterministic stand-in for the monte carlo evaluation
+#[cfg(test)]
+fn hash_evaluate(board: &Board, _own_color: Color) -> (f64, u64) {
+    ((board.canonical_key() % 1_000) as f64 / 1_000.0, 1)
+}
+
+// searches the board to the given depth with a full window
+//
+// Extensions are left out, as their cap makes the result depend on the path to a position.
+#[cfg(test)]
+fn search_depth(board: &Board, own_color: Color, depth: u8, cache: &BoardCache) -> (Vec<Column>, Score, u64, u64) {
+    let never_stop = AtomicBool::new(false);
+    let mut context = SearchContext::new(own_color, cache, &hash_evaluate, &never_stop);
+    context.options.max_extensions = 0;
+    minmax(board, depth, &[], Score::MIN, Score::MAX, &mut context)
+}
+
+#[test]
+fn test_parallel_minmax() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let never_stop = AtomicBool::new(false);
+    for depth in 1..5 {
+        let (variant, eval, _, _) = search_depth(&board, Color::Blue, depth, &BoardCache::new());
+        for &num_threads in &[2, 3] {
+            let cache = BoardCache::new();
+            let mut context = SearchContext::new(Color::Blue, &cache, &hash_evaluate, &never_stop);
+            context.options = SearchOptions { num_threads, ..SearchOptions::plain() };
+            let (parallel_variant, parallel_eval, _, _) = parallel_minmax(&board, depth, &[], Score::MIN, Score::MAX, &mut context);
+            assert_eq!(parallel_eval, eval);
+            assert_eq!(parallel_variant.len(), variant.len());
+        }
+    }
+}
+
+#[test]
+fn test_proven_scores() {
+    // red completes a line with the 7th piece
+    let board = Board::from_moves_str("445566").unwrap();
+    let (variant, eval, _, _) = search_depth(&board, Color::Red, 3, &BoardCache::new());
+    assert_eq!(variant, vec!(2));
+    assert_eq!(eval, Score::win_at(7));
+    assert_eq!(format!("{}", eval.relative_to(board.num_pieces())), "wins in 1");
+
+    // blue can only block one side, the 7th piece wins for red anyway
+    let board = Board::from_moves_str("44556").unwrap();
+    let (_, eval, _, _) = search_depth(&board, Color::Blue, 3, &BoardCache::new());
+    assert_eq!(eval, Score::loss_at(7));
+    assert_eq!(format!("{}", eval.relative_to(board.num_pieces())), "loses in 1");
+
+    // the last move fills the board without a winner, which ends the search right away
+    let mut board = Board::new();
+    board.play_moves(&[0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 2, 3, 2, 3, 2, 3, 3, 2, 3, 2, 3, 2, 4, 5, 4, 5, 4, 5, 5, 4, 5, 4, 5, 4, 6, 6, 6, 6, 6]);
+    let (variant, eval, _, _) = iterative_minmax(&board, Color::Red, 60_000, &BoardCache::new(), &hash_evaluate, &mut SilentObserver,
+                                                 &AtomicBool::new(false), SearchOptions::default());
+    assert_eq!((variant, eval), (vec!(6), Score::DRAW));
+    assert_eq!(format!("{}", eval.relative_to(board.num_pieces())), "draw");
+}
+
+#[test]
+fn test_draw_hides_deeper_win() {
+    use row4::config::BoardConfig;
+
+    // on a 4x3 board with 3 in a row to win, a forced line ends in a draw within the first depth,
+    // while blue wins with the last piece when every move is searched to the end
+    let mut board = Board::with_config(BoardConfig::new(4, 3, 3).unwrap());
+    board.play_moves(&[0, 2, 1, 1, 2, 3, 2]);
+    let evaluate = |_board: &Board, _own_color: Color| (0.3, 0);
+    let never_stop = AtomicBool::new(false);
+    let cache = BoardCache::new();
+    let (_, shallow, _, _) = minmax(&board, 1, &[], Score::MIN, Score::MAX, &mut SearchContext::new(Color::Blue, &cache, &evaluate, &never_stop));
+    assert_eq!(shallow, Score::DRAW);
+
+    let (_, eval, _, _) = iterative_minmax(&board, Color::Blue, 60_000, &BoardCache::new(), &evaluate, &mut SilentObserver,
+                                           &never_stop, SearchOptions::default());
+    assert_eq!(eval, Score::win_at(12));
+}
+
+#[test]
+fn test_transposition_table() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let cache = BoardCache::with_table_megabytes(1);
+
+    let (_, eval, _, positions) = search_depth(&board, Color::Blue, 4, &cache);
+    let entry = cache.table().get(&board, Color::Blue).unwrap();
+    assert_eq!((entry.score, entry.depth, entry.bound), (eval, 4, Bound::Exact));
+
+    // the same search is answered right away, a deeper one profits from the move ordering
+    let (variant, repeated_eval, _, repeated_positions) =
+        search_depth(&board, Color::Blue, 4, &cache);
+    assert_eq!((variant, repeated_eval, repeated_positions), (vec!(entry.best_move.unwrap()), eval, 1));
+
+    let (_, _, _, deeper_positions) = search_depth(&board, Color::Blue, 5, &cache);
+    let (_, _, _, fresh_positions) =
+        search_depth(&board, Color::Blue, 5, &BoardCache::with_table_megabytes(1));
+    assert!(deeper_positions < fresh_positions, "{} < {}", deeper_positions, fresh_positions);
+    assert!(positions < fresh_positions);
+}
+
+/// run with `cargo test --release -- --ignored --nocapture bench_parallel_minmax`
+#[test]
+#[ignore]
+fn bench_parallel_minmax() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let never_stop = AtomicBool::new(false);
+    let max_threads = monte_carlo::default_num_threads();
+
+    let mut single_thread_micros = 0;
+    let mut num_threads = 1;
+    while num_threads <= max_threads {
+        let timer = Timer::new();
+        let cache = BoardCache::new();
+        let evaluator = monte_carlo::MonteCarlo::new();
+        let mut context = SearchContext::new(Color::Blue, &cache, &evaluator, &never_stop);
+        context.options.num_threads = num_threads;
+        let (_, eval, _, positions) = parallel_minmax(&board, 5, &[], Score::MIN, Score::MAX, &mut context);
+        let elapsed = timer.elapsed_micros();
+        if num_threads == 1 {
+            single_thread_micros = elapsed;
+        }
+        println!("{} threads: {} ms, {} positions, eval: {:?}, speedup: {:.2}",
+                 num_threads, elapsed / 1_000, positions, eval, single_thread_micros as f64 / elapsed as f64);
+        num_threads *= 2;
+    }
+}
+
+#[test]
+fn test_move_ordering_reduces_positions() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let never_stop = AtomicBool::new(false);
+    for depth in 4..7 {
+        let cache = BoardCache::with_table_megabytes(1);
+        let mut context = SearchContext { ordering: MoveOrdering::disabled(), ..SearchContext::new(Color::Blue, &cache, &hash_evaluate, &never_stop) };
+        context.options.max_extensions = 0;
+        let (_, unordered_eval, _, unordered_positions) = minmax(&board, depth, &[], Score::MIN, Score::MAX, &mut context);
+        let (_, eval, _, positions) = search_depth(&board, Color::Blue, depth, &BoardCache::with_table_megabytes(1));
+        assert_eq!(eval, unordered_eval);
+        assert!(positions <= unordered_positions, "depth {}: {} <= {}", depth, positions, unordered_positions);
+    }
+}
+
+/// run with `cargo test --release -- --ignored --nocapture bench_move_ordering`
+#[test]
+#[ignore]
+fn bench_move_ordering() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let never_stop = AtomicBool::new(false);
+
+    // iterative deepening with and without killers and history, each keeping its ordering between depths
+    let (with_cache, without_cache) = (BoardCache::new(), BoardCache::new());
+    let evaluator = monte_carlo::MonteCarlo::new();
+    let mut with_ordering = SearchContext::new(Color::Blue, &with_cache, &evaluator, &never_stop);
+    let mut without_ordering = SearchContext { ordering: MoveOrdering::disabled(), ..SearchContext::new(Color::Blue, &without_cache, &evaluator, &never_stop) };
+    let (mut with_variant, mut without_variant) = (vec!(), vec!());
+    for depth in 1..9 {
+        let (variant, _, _, positions) = minmax(&board, depth, &with_variant, Score::MIN, Score::MAX, &mut with_ordering);
+        with_variant = variant;
+        let (variant, _, _, unordered_positions) = minmax(&board, depth, &without_variant, Score::MIN, Score::MAX, &mut without_ordering);
+        without_variant = variant;
+        println!("depth {}: {} positions with killers and history, {} without ({:.1}% fewer)",
+                 depth, positions, unordered_positions, 100.0 - 100.0 * positions as f64 / unordered_positions as f64);
+    }
+}
+
+// stops an iterative search once it completed the given depth
+#[cfg(test)]
+struct StopAtDepth<'a> {
+    depth: u8,
+    stop: &'a AtomicBool,
+    infos: Vec<SearchInfo>,
+}
+
+#[cfg(test)]
+impl<'a> SearchObserver for StopAtDepth<'a> {
+    fn on_iteration(&mut self, info: &SearchInfo) {
+        self.infos.push(info.clone());
+        if info.depth >= self.depth {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// searches iteratively up to the given depth, returning the progress of each iteration
+//
+// Only the transposition table of the cache is cleared, so that searches with different options
+// see the same leaf evaluations.
+#[cfg(test)]
+fn search_to_depth<E: Evaluator>(board: &Board, depth: u8, cache: &BoardCache, evaluator: &E, options: SearchOptions) -> Vec<SearchInfo> {
+    let stop = AtomicBool::new(false);
+    let mut observer = StopAtDepth { depth, stop: &stop, infos: vec!() };
+    cache.table().clear();
+    iterative_minmax(board, board.color_to_move, 60_000, cache, evaluator, &mut observer, &stop, options);
+    observer.infos
+}
+
+#[test]
+fn test_principal_variation_and_aspiration_windows() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let cache = BoardCache::new();
+    let plain = search_to_depth(&board, 7, &cache, &hash_evaluate, SearchOptions::plain());
+    let all_options = [
+        SearchOptions { aspiration_windows: true, ..SearchOptions::plain() },
+        SearchOptions { principal_variation: true, ..SearchOptions::plain() },
+        SearchOptions { principal_variation: true, aspiration_windows: true, ..SearchOptions::plain() },
+    ];
+    for options in &all_options {
+        let infos = search_to_depth(&board, 7, &cache, &hash_evaluate, *options);
+        let evals: Vec<Score> = infos.iter().map(|info| info.eval).collect();
+        assert_eq!(evals, plain.iter().map(|info| info.eval).collect::<Vec<_>>(), "{:?}", options);
+    }
+
+    let with_null_windows = search_to_depth(&board, 7, &cache, &hash_evaluate, all_options[1]);
+    assert!(with_null_windows.last().unwrap().positions < plain.last().unwrap().positions);
+}
+
+/// run with `cargo test --release -- --ignored --nocapture bench_search_options`
+#[test]
+#[ignore]
+fn bench_search_options() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let cache = BoardCache::new();
+    let evaluator = monte_carlo::MonteCarlo::new();
+    let all_options = [
+        SearchOptions::plain(),
+        SearchOptions { principal_variation: true, ..SearchOptions::plain() },
+        SearchOptions { aspiration_windows: true, ..SearchOptions::plain() },
+        SearchOptions { max_extensions: 0, ..SearchOptions::default() },
+        SearchOptions::default(),
+    ];
+    for options in &all_options {
+        let infos = search_to_depth(&board, 9, &cache, &evaluator, *options);
+        println!("principal variation: {}, aspiration windows: {}, extensions: {}, positions: {}, per depth: {:?}",
+                 options.principal_variation, options.aspiration_windows, options.max_extensions, infos.last().unwrap().positions,
+                 infos.iter().map(|info| info.depth_positions).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_extensions() {
+    let never_stop = AtomicBool::new(false);
+    let search = |board: &Board, depth, max_extensions| {
+        let cache = BoardCache::new();
+        let mut context = SearchContext::new(board.color_to_move, &cache, &hash_evaluate, &never_stop);
+        context.options.max_extensions = max_extensions;
+        minmax(board, depth, &[], Score::MIN, Score::MAX, &mut context)
+    };
+
+    // the winning move is forced, so it gets played instead of evaluating the position
+    let board = Board::from_moves_str("445566").unwrap();
+    assert_eq!(search(&board, 0, MAX_EXTENSIONS).1, Score::win_at(7));
+    assert!(!search(&board, 0, 0).1.is_proven());
+
+    // red threatens on both sides of three in a row, which only shows two plies beyond the horizon
+    let board = Board::from_moves_str("4455").unwrap();
+    let (variant, eval, _, _) = search(&board, 1, MAX_EXTENSIONS);
+    assert_eq!(eval, Score::win_at(7));
+    assert_eq!(variant.len(), 3);
+    assert!(!search(&board, 1, 0).1.is_proven());
+    assert!(!search(&board, 1, 1).1.is_proven());
+}
+
+#[test]
+fn test_evaluator_state() {
+    use row4::evaluator::Counting;
+
+    // each leaf is evaluated once, later visits are answered by the cache
+    let board = Board::from_moves_str("4453").unwrap();
+    let cache = BoardCache::new();
+    let counting = Counting::new(hash_evaluate);
+    let never_stop = AtomicBool::new(false);
+    let (_, _, moves_played, positions) = minmax(&board, 4, &[], Score::MIN, Score::MAX, &mut SearchContext::new(Color::Red, &cache, &counting, &never_stop));
+    assert_eq!(counting.evaluations(), cache.len() as u64);
+    assert_eq!(counting.moves_played(), moves_played);
+    assert!(counting.evaluations() <= positions);
+}
+
+#[test]
+fn test_multi_pv_minmax() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let never_stop = AtomicBool::new(false);
+    let cache = BoardCache::new();
+    let mut context = SearchContext::new(Color::Red, &cache, &hash_evaluate, &never_stop);
+    context.options.max_extensions = 0;
+    let (ranked, _, positions) = multi_pv_minmax(&board, 4, &[], 3, &mut context);
+    assert_eq!(ranked.len(), 3);
+    assert!(positions > 0);
+
+    // red is to move, so the ranking goes from the highest eval down: the best move is the one a plain
+    // search finds, and each alternative has its exact eval
+    let (_, best_eval, _, _) = search_depth(&board, Color::Red, 4, &BoardCache::new());
+    assert_eq!(ranked[0].1, best_eval);
+    for (variant, eval) in &ranked {
+        let mut sim = board;
+        sim.play_move(*variant.last().unwrap());
+        assert_eq!(search_depth(&sim, Color::Red, 3, &BoardCache::new()).1, *eval);
+    }
+    assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+
+    // all moves can be ranked
+    let (all_ranked, _, _) = multi_pv_minmax(&board, 2, &[], 7, &mut context);
+    let mut columns: Vec<Column> = all_ranked.iter().map(|(variant, _)| *variant.last().unwrap()).collect();
+    columns.sort();
+    assert_eq!(columns, vec!(0, 1, 2, 3, 4, 5, 6));
+
+    // splitting up the root between threads finds the same evals
+    let evals = |ranked: &[(Vec<Column>, Score)]| ranked.iter().map(|&(_, eval)| eval).collect::<Vec<_>>();
+    for &num_threads in &[2, 3] {
+        let cache = BoardCache::new();
+        let mut context = SearchContext::new(Color::Red, &cache, &hash_evaluate, &never_stop);
+        context.options = SearchOptions { num_threads, max_extensions: 0, ..SearchOptions::default() };
+        let (parallel_ranked, _, _) = multi_pv_minmax(&board, 4, &[], 3, &mut context);
+        assert_eq!(evals(&parallel_ranked), evals(&ranked));
+    }
+}
+
+#[test]
+fn test_analyze() {
+    // red wins right away on either side, or a move later after playing anywhere else
+    let board = Board::from_moves_str("445566").unwrap();
+    let options = SearchOptions { multi_pv: 3, ..SearchOptions::default() };
+    let ranked = analyze(&board, 5_000, &BoardCache::new(), &hash_evaluate, &mut SilentObserver, options);
+    let evals: Vec<Score> = ranked.iter().map(|ranked| ranked.eval).collect();
+    assert_eq!(evals, vec!(Score::win_at(7), Score::win_at(7), Score::win_at(9)));
+    let mut winning_moves = vec!(ranked[0].variant[0], ranked[1].variant[0]);
+    winning_moves.sort();
+    assert_eq!(winning_moves, vec!(2, 6));
+    assert_eq!(ranked[2].variant.len(), 3);
+
+    // the same ranking comes out on more than one thread, even with aspiration windows asked for
+    let parallel_options = SearchOptions { num_threads: 3, ..options };
+    let parallel_ranked = analyze(&board, 5_000, &BoardCache::new(), &hash_evaluate, &mut SilentObserver, parallel_options);
+    assert_eq!(parallel_ranked.iter().map(|ranked| ranked.eval).collect::<Vec<_>>(), evals);
+    let mut parallel_winning_moves = vec!(parallel_ranked[0].variant[0], parallel_ranked[1].variant[0]);
+    parallel_winning_moves.sort();
+    assert_eq!(parallel_winning_moves, winning_moves);
+}
+
+#[test]
+fn test_normalized_options() {
+    let options = SearchOptions { num_threads: 0, multi_pv: 3, ..SearchOptions::default() };
+    assert_eq!(options.normalized(), SearchOptions { num_threads: 1, aspiration_windows: false, ..options });
+    let options = SearchOptions { multi_pv: 0, ..SearchOptions::default() };
+    assert_eq!(options.normalized(), SearchOptions::default());
+}
@@ -4,10 +4,27 @@ pub mod monte_carlo;
 pub mod minmax;
 pub mod time;
 pub mod cache;
+pub mod config;
+pub mod notation;
+pub mod solver;
+pub mod mcts;
+pub mod engine;
+pub mod score;
+pub mod transposition;
+pub mod ordering;
+pub mod heuristic;
+pub mod evaluator;
 
 pub type Column = u8;
 
-const COLUMNS: [Column; 7] = [3, 2, 4, 1, 5, 0, 6];
+/// columns of the standard board, starting at the centre and moving outwards
+pub const COLUMNS: [Column; 7] = [3, 2, 4, 1, 5, 0, 6];
+
+/// the widest board that is supported
+pub const MAX_WIDTH: usize = 9;
+
+/// the maximum number of positions on any board
+pub const MAX_CELLS: usize = 64;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Color {
@@ -1,46 +1,175 @@
 use row4::*;
-use row4::board::Board;
+use row4::board::{Board, GameStatus};
+use row4::evaluator::Evaluator;
 
-use rand::{thread_rng, Rng};
-use std::thread;
-use std::sync::mpsc;
+use rand::{thread_rng, Rng, SeedableRng, XorShiftRng};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
 
-const NUM_THREADS: usize = 4;
+/// the number of random games per evaluation of a `MonteCarlo` evaluator, unless configured otherwise
+pub const DEFAULT_GAMES: u32 = 80;
 
-pub fn evaluate_in_parallel(board: &Board, own_color: Color, num_games: u32) -> (f64, u64) {
-    let (sender, receiver) = mpsc::channel();
+/// the number of threads to search and play games with, from the `ROW4_THREADS` environment
+/// variable or else the number of available cores
+pub fn default_num_threads() -> usize {
+    num_threads_from(env::var("ROW4_THREADS").ok())
+}
+
+fn num_threads_from(setting: Option<String>) -> usize {
+    setting.and_then(|value| value.trim().parse().ok())
+        .filter(|&threads| threads > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map(|cores| cores.get()).unwrap_or(1))
+}
+
+// a batch of playouts, answered with the number of wins and moves played
+struct Job {
+    board: Board,
+    own_color: Color,
+    num_games: u32,
+    result: Sender<(f64, u64)>,
+}
+
+/// A fixed set of worker threads playing random games in batches.
+pub struct PlayoutPool {
+    jobs: Mutex<Option<Sender<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl PlayoutPool {
+    pub fn new(num_threads: usize) -> PlayoutPool {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..usize::max(1, num_threads)).map(|_| {
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || PlayoutPool::work(&receiver))
+        }).collect();
+
+        PlayoutPool { jobs: Mutex::new(Some(sender)), workers }
+    }
+
+    fn work(receiver: &Mutex<Receiver<Job>>) {
+        loop {
+            // the lock is only held while waiting for the next job
+            let job = match receiver.lock().unwrap().recv() {
+                Ok(job) => job,
+                Err(_) => return
+            };
+            let (eval, moves) = evaluate(&job.board, job.own_color, job.num_games);
+            let _ = job.result.send((eval * job.num_games as f64, moves));
+        }
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// evaluates the position like `evaluate`, with the games split up between the workers
+    pub fn evaluate(&self, board: &Board, own_color: Color, num_games: u32) -> (f64, u64) {
+        let (sender, receiver) = mpsc::channel();
+        let num_jobs = u32::max(1, u32::min(num_games, self.num_threads() as u32));
+        {
+            let jobs = self.jobs.lock().unwrap();
+            let jobs = jobs.as_ref().unwrap();
+            for index in 0..num_jobs {
+                // spread the remainder over the first jobs
+                let batch = num_games / num_jobs + if index < num_games % num_jobs { 1 } else { 0 };
+                jobs.send(Job { board: *board, own_color, num_games: batch, result: sender.clone() }).unwrap();
+            }
+        }
+
+        let mut sum_wins = 0.0;
+        let mut sum_moves = 0;
+        for _ in 0..num_jobs {
+            let (wins, moves) = receiver.recv().unwrap();
+            sum_wins += wins;
+            sum_moves += moves;
+        }
+        (sum_wins / num_games as f64, sum_moves)
+    }
+}
 
-    for _core in 0..NUM_THREADS {
-        let core_sender = sender.clone();
-        let core_board = board.clone();
-        thread::spawn(move || {
-            let result = evaluate(&core_board, own_color, num_games / NUM_THREADS as u32);
-            core_sender.send(result).unwrap();
-        });
+impl Drop for PlayoutPool {
+    fn drop(&mut self) {
+        // closing the channel lets the workers finish
+        self.jobs.lock().unwrap().take();
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
     }
+}
 
-    let mut sum_eval = 0.0;
-    let mut sum_games = 0;
-    for _core in 0..NUM_THREADS {
-        let (eval, games) = receiver.recv().unwrap();
-        sum_eval += eval;
-        sum_games += games;
+/// Evaluates positions by the share of random games that are won.
+///
+/// The games are played on the calling thread unless the evaluator has its own workers.
+/// Alternatively, the games can be played with random numbers from a seed, always on the
+/// calling thread. The results of a single-threaded search can be repeated then, while
+/// the threads of a parallel search take turns with the generator in no particular order.
+pub struct MonteCarlo {
+    num_games: u32,
+    pool: Option<PlayoutPool>,
+    rng: Option<Mutex<XorShiftRng>>,
+}
+
+impl Default for MonteCarlo {
+    fn default() -> MonteCarlo {
+        MonteCarlo::new()
+    }
+}
+
+impl MonteCarlo {
+    pub fn new() -> MonteCarlo {
+        MonteCarlo { num_games: DEFAULT_GAMES, pool: None, rng: None }
+    }
+
+    /// plays the given number of games per evaluation, at least one
+    pub fn with_games(self, num_games: u32) -> MonteCarlo {
+        MonteCarlo { num_games: u32::max(1, num_games), ..self }
+    }
+
+    /// plays the games on a pool of the given number of worker threads, dropping any seed
+    pub fn with_threads(self, num_threads: usize) -> MonteCarlo {
+        MonteCarlo { pool: Some(PlayoutPool::new(num_threads)), rng: None, ..self }
+    }
+
+    /// plays the games with random numbers from the given seed on the calling thread, dropping any workers
+    pub fn with_seed(self, seed: u64) -> MonteCarlo {
+        // the generator must not be seeded with zeros only
+        let rng = XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x726f_7734, 0x6d63_6172]);
+        MonteCarlo { rng: Some(Mutex::new(rng)), pool: None, ..self }
+    }
+
+    pub fn num_games(&self) -> u32 {
+        self.num_games
+    }
+}
+
+impl Evaluator for MonteCarlo {
+    fn evaluate(&self, board: &Board, own_color: Color) -> (f64, u64) {
+        match (&self.rng, &self.pool) {
+            (Some(rng), _) => evaluate_with(board, own_color, self.num_games, &mut *rng.lock().unwrap()),
+            (_, Some(pool)) => pool.evaluate(board, own_color, self.num_games),
+            (None, None) => evaluate(board, own_color, self.num_games)
+        }
     }
-    (sum_eval / NUM_THREADS as f64, sum_games)
 }
 
 /// evaluate the current position, using monte carlo simulation
 pub fn evaluate(board: &Board, own_color: Color, num_games: u32) -> (f64, u64) {
+    evaluate_with(board, own_color, num_games, &mut thread_rng())
+}
+
+fn evaluate_with<R: Rng>(board: &Board, own_color: Color, num_games: u32, rng: &mut R) -> (f64, u64) {
     let mut wins = 0u32;
     let mut moves = 0u64;
     let mut total = 0u32;
 
     while total < num_games {
-        let mut sim = board.clone();
-        let (variant, result) = play_random_game(&mut sim);
-        match result {
-            Some(color) if color == own_color => wins += 1,
-            _ => ()
+        let mut sim = *board;
+        let (variant, result) = play_random_game_with(&mut sim, rng);
+        if result == GameStatus::Won(own_color) {
+            wins += 1;
         }
         total += 1;
         moves += variant.len() as u64;
@@ -50,64 +179,174 @@ pub fn evaluate(board: &Board, own_color: Color, num_games: u32) -> (f64, u64) {
 }
 
 // play a random game
-fn play_random_game(board: &mut Board) -> (Vec<Column>, Option<Color>) {
+pub fn play_random_game(board: &mut Board) -> (Vec<Column>, GameStatus) {
+    play_random_game_with(board, &mut thread_rng())
+}
+
+fn play_random_game_with<R: Rng>(board: &mut Board, rng: &mut R) -> (Vec<Column>, GameStatus) {
     let mut protocol = Vec::new();
-    loop {
-        let moves = useful_moves(&board);
-        match thread_rng().choose(&moves) {
-            None => return (protocol, None),
-            Some(&column) => {
-                protocol.push(column);
-                board.play_move(column, true);
-                if board.winner.is_some() {
-                    return (protocol, board.winner);
-                }
-            }
-        }
+    while board.status == GameStatus::Ongoing {
+        let moves = useful_moves(board);
+        let column = *rng.choose(&moves).unwrap();
+        protocol.push(column);
+        board.play_move(column);
     }
+    (protocol, board.status)
 }
 
 // restrict full list of moves to only moves that win, or do not lose immediately
 pub fn useful_moves(board: &Board) -> Vec<Column> {
+    let moves = board.moves.moves();
+
     // check if we have won with any of the moves
-    for column in board.moves.moves() {
-        let mut sim = board.clone();
-        sim.play_move(column, false);
-        if sim.winner.is_some() {
+    for &column in &moves {
+        if board.is_winning_move(column, board.color_to_move) {
             return vec!(column);
         }
     }
 
     // check if the opponent would win with any of the moves
-    for column in board.moves.moves() {
-        let mut sim = board.clone();
-        sim.color_to_move = sim.color_to_move.switch();
-        sim.play_move(column, false);
-        if sim.winner.is_some() {
+    for &column in &moves {
+        if board.is_winning_move(column, board.color_to_move.switch()) {
             return vec!(column);
         }
     }
 
-    board.moves.moves()
+    moves
+}
+
+#[test]
+fn test_num_threads_from() {
+    assert_eq!(num_threads_from(Some("3".to_string())), 3);
+    assert!(num_threads_from(Some("0".to_string())) >= 1);
+    assert!(num_threads_from(Some("many".to_string())) >= 1);
+    assert!(num_threads_from(None) >= 1);
+}
+
+#[test]
+fn test_pool_evaluate() {
+    let pool = PlayoutPool::new(3);
+    assert_eq!(pool.num_threads(), 3);
+
+    // red wins right away in every game
+    let board = Board::from_moves_str("445566").unwrap();
+    assert_eq!(pool.evaluate(&board, Color::Red, 10), (1.0, 10));
+    assert_eq!(pool.evaluate(&board, Color::Blue, 2), (0.0, 2));
+
+    let (eval, moves) = pool.evaluate(&Board::new(), Color::Red, 80);
+    assert!(eval > 0.0 && eval < 1.0);
+    assert!(moves >= 80 * 7);
+}
+
+#[test]
+fn test_monte_carlo() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let (eval, moves) = MonteCarlo::new().with_games(10).evaluate(&board, Color::Red);
+    assert!((0.0..=1.0).contains(&eval));
+    assert!(moves >= 10 * 3);
+
+    // red wins right away in every game, on the workers as well
+    let won = Board::from_moves_str("445566").unwrap();
+    assert_eq!(MonteCarlo::new().with_threads(2).evaluate(&won, Color::Red), (1.0, DEFAULT_GAMES as u64));
+
+    // the same seed plays the same games
+    let first = MonteCarlo::new().with_seed(42);
+    let second = MonteCarlo::new().with_threads(2).with_seed(42);
+    assert!(second.pool.is_none());
+    for _ in 0..3 {
+        assert_eq!(first.evaluate(&board, Color::Blue), second.evaluate(&board, Color::Blue));
+    }
+
+    // workers can't play the games of a seed in order, so they replace it
+    let threaded = MonteCarlo::new().with_seed(42).with_threads(2);
+    assert!(threaded.rng.is_none());
+    assert_eq!(threaded.pool.as_ref().map(PlayoutPool::num_threads), Some(2));
 }
 
 #[test]
 fn test_useful_moves_win() {
     let mut board = Board::new();
-    board.play_moves(&vec!(4, 3, 4, 3, 4, 3));
+    board.play_moves(&[4, 3, 4, 3, 4, 3]);
     assert_eq!(useful_moves(&board), vec!(4));
 }
 
 #[test]
 fn test_useful_moves_avoid_losing() {
     let mut board = Board::new();
-    board.play_moves(&vec!(4, 3, 4, 3, 4));
+    board.play_moves(&[4, 3, 4, 3, 4]);
     assert_eq!(useful_moves(&board), vec!(4));
 }
 
 #[test]
 fn test_useful_moves_normal() {
     let mut board = Board::new();
-    board.play_moves(&vec!(4, 3, 4, 3, 5, 2));
+    board.play_moves(&[4, 3, 4, 3, 5, 2]);
     assert_eq!(useful_moves(&board), COLUMNS.to_vec());
 }
+
+/// run with `cargo test --release -- --ignored --nocapture bench_evaluate`
+#[test]
+#[ignore]
+fn bench_evaluate() {
+    use row4::time::Timer;
+
+    let mut board = Board::new();
+    board.play_moves(&[3, 3, 2, 4]);
+
+    let timer = Timer::new();
+    let (eval, moves) = evaluate(&board, Color::Red, 20_000);
+    let elapsed = timer.elapsed_micros();
+
+    println!("20000 playouts in {} ms ({} playouts/s, {} moves/s), eval: {}",
+             elapsed / 1_000, 20_000 * 1_000_000 / elapsed, moves * 1_000_000 / elapsed, eval);
+
+    // the pieces of the side to move after each move of the playouts, with the position of that move
+    let mut checks = Vec::new();
+    for _ in 0..20_000 {
+        let mut sim = board;
+        let (variant, _) = play_random_game(&mut sim);
+        let mut sim = board;
+        for column in variant {
+            let height = sim.height(column);
+            let bits = match sim.color_to_move {
+                Color::Red => sim.red,
+                Color::Blue => sim.blue
+            };
+            checks.push((bits | sim.config.position_mask(column, height), column, height));
+            sim.play_move(column);
+        }
+    }
+
+    let timer = Timer::new();
+    let full_wins = checks.iter().filter(|&&(bits, _, _)| board.has_line(bits)).count();
+    let full_elapsed = u64::max(1, timer.elapsed_micros());
+    let timer = Timer::new();
+    let wins = checks.iter().filter(|&&(bits, column, height)| board.is_line_at(bits, column, height)).count();
+    let elapsed = u64::max(1, timer.elapsed_micros());
+    assert_eq!(wins, full_wins);
+
+    println!("{} win checks: whole board in {} µs ({} checks/s), through the new piece in {} µs ({} checks/s)",
+             checks.len(), full_elapsed, checks.len() as u64 * 1_000_000 / full_elapsed,
+             elapsed, checks.len() as u64 * 1_000_000 / elapsed);
+}
+
+/// run with `cargo test --release -- --ignored --nocapture bench_evaluate_in_parallel`
+#[test]
+#[ignore]
+fn bench_evaluate_in_parallel() {
+    use row4::time::Timer;
+
+    let mut board = Board::new();
+    board.play_moves(&[3, 3, 2, 4]);
+    let num_threads = default_num_threads();
+    let evaluator = MonteCarlo::new().with_threads(num_threads);
+
+    let timer = Timer::new();
+    for _ in 0..1_000 {
+        evaluator.evaluate(&board, Color::Red);
+    }
+    let elapsed = timer.elapsed_micros();
+
+    println!("1000 evaluations of {} playouts on {} threads in {} ms ({} evaluations/s)",
+             DEFAULT_GAMES, num_threads, elapsed / 1_000, 1_000 * 1_000_000 / elapsed);
+}
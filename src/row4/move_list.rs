@@ -1,19 +1,20 @@
 use row4::*;
+use row4::config::BoardConfig;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct MoveList {
-    moves: [Option<Column>; 7],
+    moves: [Option<Column>; MAX_WIDTH],
     len: u8
 }
 
 impl MoveList {
-    pub fn full() -> MoveList {
-        MoveList::from(&COLUMNS)
+    pub fn full(config: BoardConfig) -> MoveList {
+        MoveList::from(config.columns())
     }
 
     pub fn from(moves: &[Column]) -> MoveList {
-        let mut ml = [None; 7];
-        for i in 0..7 {
+        let mut ml = [None; MAX_WIDTH];
+        for i in 0..MAX_WIDTH {
             if i < moves.len() {
                 ml[i] = Some(moves[i]);
             } else {
@@ -27,13 +28,17 @@ impl MoveList {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn moves(&self) -> Vec<Column> {
         let mut moves = Vec::new();
         let mut index = 0;
         while let Some(column) = self.moves[index] {
             moves.push(column);
             index += 1;
-            if index == 7 { break };
+            if index == MAX_WIDTH { break };
         }
         moves
     }
@@ -41,8 +46,8 @@ impl MoveList {
 
 #[test]
 fn test_full_move_list() {
-    let full = MoveList::full();
-    assert_eq!(full.moves.to_vec(), COLUMNS.iter().map(|&x| Some(x)).collect::<Vec<_>>());
+    let full = MoveList::full(BoardConfig::standard());
+    assert_eq!(full.moves[..7].to_vec(), COLUMNS.iter().map(|&x| Some(x)).collect::<Vec<_>>());
     assert_eq!(full.moves(), COLUMNS.to_vec());
     assert_eq!(full.len(), 7);
 }
@@ -50,7 +55,7 @@ fn test_full_move_list() {
 #[test]
 fn test_empty_move_list() {
     let empty = MoveList::from(&[]);
-    assert_eq!(empty.moves, [None; 7]);
+    assert_eq!(empty.moves, [None; MAX_WIDTH]);
     assert_eq!(empty.moves(), vec!());
     assert_eq!(empty.len(), 0);
 }
@@ -58,7 +63,14 @@ fn test_empty_move_list() {
 #[test]
 fn test_partial_move_list() {
     let moves = MoveList::from(&[3, 1, 6]);
-    assert_eq!(moves.moves, [Some(3), Some(1), Some(6), None, None, None, None]);
+    assert_eq!(moves.moves, [Some(3), Some(1), Some(6), None, None, None, None, None, None]);
     assert_eq!(moves.moves(), vec!(3, 1, 6));
     assert_eq!(moves.len(), 3);
 }
+
+#[test]
+fn test_wide_move_list() {
+    let full = MoveList::full(BoardConfig::new(9, 7, 4).unwrap());
+    assert_eq!(full.moves(), vec!(4, 3, 5, 2, 6, 1, 7, 0, 8));
+    assert_eq!(full.len(), 9);
+}
@@ -0,0 +1,222 @@
+use std::error::Error;
+use std::fmt;
+
+use row4::*;
+use row4::board::{Board, MoveError};
+use row4::config::BoardConfig;
+
+/// Reasons why a position in move notation or a board diagram can't be read.
+///
+/// Indices refer to the character position within the parsed string, starting at 0.
+/// Rows and columns of diagrams start at 0, with row 0 at the bottom.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    InvalidCharacter { index: usize, character: char },
+    ColumnOutOfRange { index: usize, column: u32 },
+    ColumnFull { index: usize, column: u32 },
+    GameOver { index: usize },
+    UnevenRows { row: usize },
+    UnsupportedSize { width: usize, height: usize },
+    FloatingPiece { column: Column, row: u8 },
+    PieceCount { red: u32, blue: u32 },
+    BothSidesWon,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::InvalidCharacter { index, character } =>
+                write!(dest, "invalid character '{}' at position {}", character, index + 1),
+            ParseError::ColumnOutOfRange { index, column } =>
+                write!(dest, "column {} at position {} is not on the board", column, index + 1),
+            ParseError::ColumnFull { index, column } =>
+                write!(dest, "column {} at position {} is already full", column, index + 1),
+            ParseError::GameOver { index } =>
+                write!(dest, "move at position {} is played after the game is over", index + 1),
+            ParseError::UnevenRows { row } =>
+                write!(dest, "row {} from the bottom has a different width than the others", row + 1),
+            ParseError::UnsupportedSize { width, height } =>
+                write!(dest, "a board of {}x{} is not supported", width, height),
+            ParseError::FloatingPiece { column, row } =>
+                write!(dest, "the piece in column {}, row {} has nothing below it", column + 1, row + 1),
+            ParseError::PieceCount { red, blue } =>
+                write!(dest, "{} red pieces (x) and {} blue pieces (o) can't be the result of alternating moves", red, blue),
+            ParseError::BothSidesWon =>
+                write!(dest, "both sides have completed a line"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl Board {
+    /// creates a standard board from a sequence of moves, e.g. "4453"
+    ///
+    /// Each digit is the 1-based column of a move, starting with red.
+    pub fn from_moves_str(moves: &str) -> Result<Board, ParseError> {
+        let mut board = Board::new();
+        board.play_moves_str(moves)?;
+        Ok(board)
+    }
+
+    /// play a series of moves given as 1-based column digits
+    /// the board stays unchanged if any of the moves can't be played
+    pub fn play_moves_str(&mut self, moves: &str) -> Result<(), ParseError> {
+        let mut board = *self;
+        for (index, character) in moves.trim().chars().enumerate() {
+            let column = match character.to_digit(10) {
+                Some(0) => return Err(ParseError::ColumnOutOfRange { index, column: 0 }),
+                Some(column) => column,
+                None => return Err(ParseError::InvalidCharacter { index, character })
+            };
+            board.try_play_move(column as Column - 1).map_err(|error| match error {
+                MoveError::ColumnOutOfRange => ParseError::ColumnOutOfRange { index, column },
+                MoveError::ColumnFull => ParseError::ColumnFull { index, column },
+                MoveError::GameOver => ParseError::GameOver { index }
+            })?;
+        }
+        *self = board;
+        Ok(())
+    }
+
+    /// reads a board from a diagram as printed by its `Display` implementation
+    ///
+    /// Rows are given from top to bottom, with `x` for red, `o` for blue and `.` for empty
+    /// positions. Whitespace between the positions is ignored. The board size follows from
+    /// the diagram, with 4 in a row to win. The move history of the board stays empty.
+    pub fn from_diagram(diagram: &str) -> Result<Board, ParseError> {
+        let mut rows = vec!(Vec::new());
+        for (index, character) in diagram.chars().enumerate() {
+            match character {
+                '\n' => rows.push(Vec::new()),
+                'x' | 'o' | '.' => rows.last_mut().unwrap().push(character),
+                _ if character.is_whitespace() => (),
+                _ => return Err(ParseError::InvalidCharacter { index, character })
+            }
+        }
+        rows.retain(|row| !row.is_empty());
+        rows.reverse();
+
+        let width = rows.first().map_or(0, |row| row.len());
+        if let Some(row) = rows.iter().position(|row| row.len() != width) {
+            return Err(ParseError::UnevenRows { row });
+        }
+        let config = if width <= MAX_WIDTH && rows.len() <= MAX_CELLS {
+            BoardConfig::new(width as u8, rows.len() as u8, 4)
+        } else {
+            None
+        };
+        let config = match config {
+            Some(config) => config,
+            None => return Err(ParseError::UnsupportedSize { width, height: rows.len() })
+        };
+
+        let mut red = 0;
+        let mut blue = 0;
+        for (row, cells) in rows.iter().enumerate() {
+            for (column, &cell) in cells.iter().enumerate() {
+                let mask = config.position_mask(column as Column, row as u8);
+                match cell {
+                    'x' => red |= mask,
+                    'o' => blue |= mask,
+                    _ => ()
+                }
+                if cell != '.' && row > 0 && rows[row - 1][column] == '.' {
+                    return Err(ParseError::FloatingPiece { column: column as Column, row: row as u8 });
+                }
+            }
+        }
+
+        let (num_red, num_blue) = (u64::count_ones(red), u64::count_ones(blue));
+        if num_red != num_blue && num_red != num_blue + 1 {
+            return Err(ParseError::PieceCount { red: num_red, blue: num_blue });
+        }
+        if config.has_line(red) && config.has_line(blue) {
+            return Err(ParseError::BothSidesWon);
+        }
+
+        Ok(Board::from_bits(config, red, blue))
+    }
+
+    /// returns the moves played on this board as 1-based column digits
+    pub fn to_moves_str(&self) -> String {
+        self.played_moves().iter().map(|&column| (b'1' + column) as char).collect()
+    }
+}
+
+#[test]
+fn test_from_moves_str() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let mut expected = Board::new();
+    expected.play_moves(&[3, 3, 4, 2]);
+    assert_eq!(board, expected);
+    assert_eq!(board.to_moves_str(), "4453");
+
+    assert_eq!(Board::from_moves_str("").unwrap(), Board::new());
+    assert_eq!(Board::from_moves_str(" 1234\n").unwrap().to_moves_str(), "1234");
+}
+
+#[test]
+fn test_from_moves_str_errors() {
+    assert_eq!(Board::from_moves_str("44a"), Err(ParseError::InvalidCharacter { index: 2, character: 'a' }));
+    assert_eq!(Board::from_moves_str("4408"), Err(ParseError::ColumnOutOfRange { index: 2, column: 0 }));
+    assert_eq!(Board::from_moves_str("448"), Err(ParseError::ColumnOutOfRange { index: 2, column: 8 }));
+    assert_eq!(Board::from_moves_str("1111111"), Err(ParseError::ColumnFull { index: 6, column: 1 }));
+    assert_eq!(Board::from_moves_str("54545451"), Err(ParseError::GameOver { index: 7 }));
+}
+
+#[test]
+fn test_play_moves_str_keeps_board_on_error() {
+    let mut board = Board::from_moves_str("44").unwrap();
+    assert!(board.play_moves_str("339").is_err());
+    assert_eq!(board.to_moves_str(), "44");
+}
+
+#[test]
+fn test_from_diagram() {
+    let board = Board::from_moves_str("4453").unwrap();
+    let diagram = format!("{}", board);
+    let parsed = Board::from_diagram(&diagram).unwrap();
+    assert_eq!(parsed.red, board.red);
+    assert_eq!(parsed.blue, board.blue);
+    assert_eq!(parsed.column_heights, board.column_heights);
+    assert_eq!(parsed.color_to_move, Color::Red);
+    assert_eq!(parsed.hash(), board.hash());
+    assert_eq!(format!("{}", parsed), diagram);
+
+    let small = Board::from_diagram("
+        . . . . .
+        . . . . .
+        . . x . .
+        . o x o .
+    ").unwrap();
+    assert_eq!((small.config.width(), small.config.height()), (5, 4));
+    assert_eq!(small.color_to_move, Color::Red);
+    assert_eq!(small.column_heights[..5], [0, 1, 2, 1, 0]);
+}
+
+#[test]
+fn test_from_diagram_winner() {
+    let board = Board::from_diagram("
+        . . . . . . .
+        . . . . . . .
+        . . . x . . .
+        . . . x o . .
+        . . . x o . .
+        . . . x o . .
+    ").unwrap();
+    assert_eq!(board.winner(), Some(Color::Red));
+    assert_eq!(board.color_to_move, Color::Blue);
+}
+
+#[test]
+fn test_from_diagram_errors() {
+    assert_eq!(Board::from_diagram("x o\n. y"), Err(ParseError::InvalidCharacter { index: 6, character: 'y' }));
+    assert_eq!(Board::from_diagram(". . . .\n. . .\nx o x o"), Err(ParseError::UnevenRows { row: 1 }));
+    assert_eq!(Board::from_diagram(""), Err(ParseError::UnsupportedSize { width: 0, height: 0 }));
+    assert_eq!(Board::from_diagram(". . . .\nx . . .\n. o . x"), Err(ParseError::FloatingPiece { column: 0, row: 1 }));
+    assert!(Board::from_diagram(". . . .\n. . . .\nx x . o").is_ok());
+    assert_eq!(Board::from_diagram(". . . .\n. . . .\nx x x o"), Err(ParseError::PieceCount { red: 3, blue: 1 }));
+    assert_eq!(Board::from_diagram(". . . .\n. . . .\no o . x"), Err(ParseError::PieceCount { red: 1, blue: 2 }));
+    assert_eq!(Board::from_diagram("x o . .\nx o . .\nx o . .\nx o . ."), Err(ParseError::BothSidesWon));
+}
@@ -0,0 +1,117 @@
+use row4::*;
+use row4::board::Board;
+
+// killer moves kept per number of pieces on the board
+const NUM_KILLERS: usize = 2;
+
+/// Remembers which moves caused cutoffs, to try them first in other positions.
+///
+/// Killer moves are the last columns that caused a cutoff with the same number of pieces
+/// on the board, which is the same ply for all positions of a search. The history counts
+/// cutoffs for each position a piece can be dropped to, weighted by the remaining depth.
+#[derive(Clone)]
+pub struct MoveOrdering {
+    enabled: bool,
+    killers: [[Option<Column>; NUM_KILLERS]; MAX_CELLS + 1],
+    // indexed by the color to move and the bit of the new piece
+    history: [[u32; MAX_CELLS]; 2],
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering::new()
+    }
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        MoveOrdering { enabled: true, killers: [[None; NUM_KILLERS]; MAX_CELLS + 1], history: [[0; MAX_CELLS]; 2] }
+    }
+
+    /// keeps moves in the order they are given, to compare searches with and without the heuristics
+    pub fn disabled() -> MoveOrdering {
+        MoveOrdering { enabled: false, ..MoveOrdering::new() }
+    }
+
+    fn color_index(color: Color) -> usize {
+        match color {
+            Color::Red => 0,
+            Color::Blue => 1
+        }
+    }
+
+    fn history_score(&self, board: &Board, column: Column) -> u32 {
+        let bit = board.config.position_mask(column, board.height(column)).trailing_zeros() as usize;
+        self.history[MoveOrdering::color_index(board.color_to_move)][bit]
+    }
+
+    /// sorts the moves with killer moves first and the others by their history,
+    /// keeping the given order between moves that are equally good
+    pub fn order(&self, board: &Board, moves: &mut [Column]) {
+        if !self.enabled {
+            return;
+        }
+        let killers = &self.killers[board.num_pieces() as usize];
+        moves.sort_by_key(|&column| {
+            let killer_rank = killers.iter().position(|&killer| killer == Some(column)).unwrap_or(NUM_KILLERS);
+            (killer_rank, u32::MAX - self.history_score(board, column))
+        });
+    }
+
+    /// remembers a move that caused a cutoff with the given remaining depth
+    pub fn record_cutoff(&mut self, board: &Board, column: Column, depth: u8) {
+        if !self.enabled {
+            return;
+        }
+        let killers = &mut self.killers[board.num_pieces() as usize];
+        if killers[0] != Some(column) {
+            killers[1] = killers[0];
+            killers[0] = Some(column);
+        }
+
+        let bit = board.config.position_mask(column, board.height(column)).trailing_zeros() as usize;
+        let entry = &mut self.history[MoveOrdering::color_index(board.color_to_move)][bit];
+        *entry = entry.saturating_add(depth as u32 * depth as u32);
+    }
+}
+
+#[test]
+fn test_killers() {
+    let board = Board::from_moves_str("44").unwrap();
+    let mut ordering = MoveOrdering::new();
+    let mut moves = COLUMNS.to_vec();
+    ordering.order(&board, &mut moves);
+    assert_eq!(moves, COLUMNS.to_vec());
+
+    ordering.record_cutoff(&board, 0, 1);
+    ordering.record_cutoff(&board, 6, 1);
+    ordering.order(&board, &mut moves);
+    assert_eq!(moves, vec!(6, 0, 3, 2, 4, 1, 5));
+
+    // killers only apply to the same ply
+    let mut moves = COLUMNS.to_vec();
+    ordering.order(&Board::from_moves_str("444").unwrap(), &mut moves);
+    assert_eq!(moves, COLUMNS.to_vec());
+}
+
+#[test]
+fn test_history() {
+    let board = Board::from_moves_str("44").unwrap();
+    let mut ordering = MoveOrdering::new();
+    ordering.record_cutoff(&board, 1, 2);
+    ordering.record_cutoff(&board, 5, 3);
+    ordering.record_cutoff(&board, 2, 1);
+    ordering.record_cutoff(&board, 3, 1);
+
+    // another position at a different ply, where the same pieces would be dropped
+    let other = Board::from_moves_str("4444").unwrap();
+    let mut moves = COLUMNS.to_vec();
+    ordering.order(&other, &mut moves);
+    assert_eq!(moves, vec!(5, 1, 2, 3, 4, 0, 6));
+
+    let mut disabled = MoveOrdering::disabled();
+    disabled.record_cutoff(&board, 1, 2);
+    let mut moves = COLUMNS.to_vec();
+    disabled.order(&board, &mut moves);
+    assert_eq!(moves, COLUMNS.to_vec());
+}
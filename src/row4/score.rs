@@ -0,0 +1,175 @@
+use std::fmt;
+
+// estimates are scaled win rates from 0 to this value, always even to leave the odd values
+// in between free for proven draws
+const ESTIMATE_SCALE: i32 = 1_000_000;
+
+// proven results lie beyond any estimate, offset by the number of pieces on the board
+const WIN: i32 = 1_000_000_000;
+
+/// The value of a position for one side, either proven or estimated.
+///
+/// Proven wins and losses remember the number of pieces on the board when the game ends,
+/// so that scores of positions at different depths compare correctly: earlier wins are
+/// better than later ones, and later losses are better than earlier ones. Estimates are
+/// win rates between 0 and 1. A proven draw has a value of its own, just above an estimated
+/// win rate of 0.5.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Score(i32);
+
+impl Score {
+    /// below any real score, used as the initial bound of a search
+    pub const MIN: Score = Score(-WIN - 1);
+
+    /// above any real score, used as the initial bound of a search
+    pub const MAX: Score = Score(WIN + 1);
+
+    /// the game ends in a draw
+    pub const DRAW: Score = Score(ESTIMATE_SCALE / 2 + 1);
+
+    /// below any other score, returned by searches that were stopped and whose results get discarded
+    pub const STOPPED: Score = Score(i32::MIN);
+
+    /// the game is won with the given number of pieces on the board
+    pub fn win_at(num_pieces: u8) -> Score {
+        Score(WIN - num_pieces as i32)
+    }
+
+    /// the game is lost with the given number of pieces on the board
+    pub fn loss_at(num_pieces: u8) -> Score {
+        Score(-WIN + num_pieces as i32)
+    }
+
+    /// an estimated win rate between 0 and 1
+    pub fn estimate(win_rate: f64) -> Score {
+        Score(2 * (win_rate.clamp(0.0, 1.0) * (ESTIMATE_SCALE / 2) as f64).round() as i32)
+    }
+
+    /// the internal representation, to store scores compactly
+    pub fn raw(self) -> i32 {
+        self.0
+    }
+
+    pub fn from_raw(raw: i32) -> Score {
+        Score(raw)
+    }
+
+    /// the score moved up by the given raw amount, or down for negative ones, to set up search windows
+    pub fn shifted(self, delta: i32) -> Score {
+        Score(self.0.saturating_add(delta))
+    }
+
+    pub fn is_win(self) -> bool {
+        self.0 > ESTIMATE_SCALE && self <= Score::win_at(0)
+    }
+
+    pub fn is_loss(self) -> bool {
+        self.0 < 0 && self >= Score::loss_at(0)
+    }
+
+    pub fn is_draw(self) -> bool {
+        self == Score::DRAW
+    }
+
+    pub fn is_proven(self) -> bool {
+        self.is_win() || self.is_loss() || self.is_draw()
+    }
+
+    /// the win rate of this score, 1 for wins, 0 for losses and 0.5 for draws
+    pub fn win_rate(self) -> f64 {
+        if self.is_win() {
+            1.0
+        } else if self.is_loss() {
+            0.0
+        } else if self.is_draw() {
+            0.5
+        } else {
+            self.0 as f64 / ESTIMATE_SCALE as f64
+        }
+    }
+
+    /// the number of pieces on the board when a proven game ends
+    pub fn end_pieces(self) -> Option<u8> {
+        if self.is_win() {
+            Some((WIN - self.0) as u8)
+        } else if self.is_loss() {
+            Some((self.0 + WIN) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// describes the score for the side to move with the given number of pieces on the board
+    pub fn relative_to(self, num_pieces: u8) -> RelativeScore {
+        RelativeScore { score: self, num_pieces }
+    }
+}
+
+/// A score as seen from a position, to print the moves until the game ends.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RelativeScore {
+    score: Score,
+    num_pieces: u8,
+}
+
+impl RelativeScore {
+    /// the number of moves of the winner until the game ends, counting the winning move
+    pub fn moves_to_end(&self) -> Option<u8> {
+        self.score.end_pieces().map(|end| end.saturating_sub(self.num_pieces).div_ceil(2))
+    }
+}
+
+impl fmt::Display for RelativeScore {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        match self.moves_to_end() {
+            Some(moves) if self.score.is_win() => write!(dest, "wins in {}", moves),
+            Some(moves) => write!(dest, "loses in {}", moves),
+            None if self.score.is_draw() => write!(dest, "draw"),
+            None => write!(dest, "win rate {:.3}", self.score.win_rate())
+        }
+    }
+}
+
+#[test]
+fn test_order() {
+    assert!(Score::MIN < Score::loss_at(7));
+    assert!(Score::loss_at(7) < Score::loss_at(9));
+    assert!(Score::loss_at(42) < Score::estimate(0.0));
+    assert!(Score::estimate(0.0) < Score::DRAW);
+    assert!(Score::estimate(0.5) < Score::DRAW);
+    assert!(Score::DRAW < Score::estimate(0.500_01));
+    assert!(Score::DRAW < Score::estimate(1.0));
+    assert!(Score::estimate(1.0) < Score::win_at(42));
+    assert!(Score::win_at(42) < Score::win_at(7));
+    assert!(Score::win_at(0) < Score::MAX);
+    assert!(Score::STOPPED < Score::MIN);
+}
+
+#[test]
+fn test_kinds() {
+    assert!(Score::win_at(9).is_win() && Score::win_at(9).is_proven());
+    assert!(Score::loss_at(9).is_loss() && !Score::loss_at(9).is_win());
+    assert!(Score::DRAW.is_draw() && Score::DRAW.is_proven() && !Score::DRAW.is_win());
+    assert!(!Score::estimate(0.5).is_proven() && !Score::estimate(1.0).is_proven());
+    assert!(!Score::MIN.is_proven() && !Score::MAX.is_proven() && !Score::STOPPED.is_proven());
+    assert_eq!(Score::DRAW.win_rate(), 0.5);
+    assert_eq!(Score::estimate(0.5).win_rate(), 0.5);
+    assert_eq!(Score::estimate(0.25).win_rate(), 0.25);
+    assert_eq!(Score::estimate(1.5), Score::estimate(1.0));
+    assert_eq!(Score::loss_at(12).win_rate(), 0.0);
+    assert_eq!(Score::win_at(12).end_pieces(), Some(12));
+    assert_eq!(Score::DRAW.end_pieces(), None);
+    assert_eq!(Score::DRAW.shifted(1).raw(), Score::DRAW.raw() + 1);
+    assert!(Score::DRAW.shifted(-1) < Score::DRAW);
+}
+
+#[test]
+fn test_relative_to() {
+    // the side to move wins with its 5th move, after 9 plies
+    assert_eq!(format!("{}", Score::win_at(21).relative_to(12)), "wins in 5");
+    assert_eq!(format!("{}", Score::win_at(13).relative_to(12)), "wins in 1");
+    // the opponent wins with its 2nd move
+    assert_eq!(format!("{}", Score::loss_at(16).relative_to(12)), "loses in 2");
+    assert_eq!(format!("{}", Score::DRAW.relative_to(12)), "draw");
+    assert_eq!(format!("{}", Score::estimate(0.5).relative_to(12)), "win rate 0.500");
+}
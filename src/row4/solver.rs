@@ -0,0 +1,547 @@
+use std::cmp::Ordering;
+
+use row4::*;
+use row4::board::{Board, GameStatus};
+use row4::time::Timer;
+
+const WIDTH: u32 = 7;
+const HEIGHT: u32 = 6;
+const SIZE: i32 = (WIDTH * HEIGHT) as i32;
+
+const MIN_SCORE: i32 = -SIZE / 2 + 3;
+const MAX_SCORE: i32 = (SIZE + 1) / 2 - 3;
+
+/// number of entries of the transposition table, a prime above 2^17 so that the
+/// 32 bit partial keys together with the index identify a position uniquely
+const TABLE_SIZE: usize = 16_777_213;
+
+/// how many positions are visited between looking at the clock
+const CLOCK_INTERVAL: u64 = 4_096;
+
+/// columns of the solver's position, starting at the centre and moving outwards
+const COLUMN_ORDER: [u32; 7] = [3, 2, 4, 1, 5, 0, 6];
+
+/// the lowest position of every column
+const BOTTOM_MASK: u64 = 0x408_1020_4081;
+const BOARD_MASK: u64 = BOTTOM_MASK * ((1 << HEIGHT) - 1);
+
+/// The exact result of a position with perfect play from both sides, seen from the side to move.
+///
+/// Wins and losses carry the number of plies until the game ends, counting the winning move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Win(u8),
+    Draw,
+    Loss(u8),
+}
+
+impl Outcome {
+    /// the same outcome seen from the other side, one ply earlier
+    fn before_move(self) -> Outcome {
+        match self {
+            Outcome::Win(plies) => Outcome::Loss(plies + 1),
+            Outcome::Draw => Outcome::Draw,
+            Outcome::Loss(plies) => Outcome::Win(plies + 1),
+        }
+    }
+
+    /// faster wins and slower losses are better
+    fn rank(self) -> i32 {
+        match self {
+            Outcome::Win(plies) => 1_000 - plies as i32,
+            Outcome::Draw => 0,
+            Outcome::Loss(plies) => -1_000 + plies as i32,
+        }
+    }
+}
+
+impl Ord for Outcome {
+    fn cmp(&self, other: &Outcome) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Outcome {
+    fn partial_cmp(&self, other: &Outcome) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A standard board in the layout the solver needs: each column takes up 7 bits,
+/// the lowest 6 of which are positions, with an always empty bit on top.
+#[derive(Copy, Clone, Debug)]
+struct Position {
+    // pieces of the side to move
+    current: u64,
+    // all pieces
+    mask: u64,
+    moves: i32,
+}
+
+impl Position {
+    fn from(board: &Board) -> Position {
+        let own_bits = match board.color_to_move {
+            Color::Red => board.red,
+            Color::Blue => board.blue
+        };
+        let mut position = Position { current: 0, mask: 0, moves: 0 };
+        for column in 0..WIDTH {
+            for row in 0..board.height(column as Column) as u32 {
+                let bit = 1 << (column * (HEIGHT + 1) + row);
+                position.mask |= bit;
+                if own_bits & board.config.position_mask(column as Column, row as u8) != 0 {
+                    position.current |= bit;
+                }
+                position.moves += 1;
+            }
+        }
+        position
+    }
+
+    fn column_mask(column: u32) -> u64 {
+        ((1 << HEIGHT) - 1) << (column * (HEIGHT + 1))
+    }
+
+    /// plays the move given as single bit of a free position
+    fn play(&mut self, move_bit: u64) {
+        self.current ^= self.mask;
+        self.mask |= move_bit;
+        self.moves += 1;
+    }
+
+    /// a unique key for the position, shared with its mirror image
+    fn key(&self) -> u64 {
+        // within each column, the sum can't carry over into the next column
+        let key = self.current + self.mask;
+        let mut mirrored = 0;
+        for column in 0..WIDTH {
+            mirrored |= ((key >> (column * (HEIGHT + 1))) & 0x7f) << ((WIDTH - 1 - column) * (HEIGHT + 1));
+        }
+        u64::min(key, mirrored)
+    }
+
+    /// all positions where a piece can be played next
+    fn possible(&self) -> u64 {
+        (self.mask + BOTTOM_MASK) & BOARD_MASK
+    }
+
+    fn can_win_next(&self) -> bool {
+        Position::winning_positions(self.current, self.mask) & self.possible() != 0
+    }
+
+    /// all moves that don't allow the opponent to win right away
+    fn possible_non_losing_moves(&self) -> u64 {
+        let mut possible = self.possible();
+        let opponent_wins = Position::winning_positions(self.current ^ self.mask, self.mask);
+        let forced = possible & opponent_wins;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                return 0; // two threats can't both be blocked
+            }
+            possible = forced;
+        }
+        possible & !(opponent_wins >> 1) // don't play directly below a threat
+    }
+
+    /// the number of threats the side to move has after playing the move
+    fn move_score(&self, move_bit: u64) -> u32 {
+        Position::winning_positions(self.current | move_bit, self.mask).count_ones()
+    }
+
+    /// all free positions that would complete four in a row for the given pieces
+    fn winning_positions(position: u64, mask: u64) -> u64 {
+        // vertical
+        let mut result = (position << 1) & (position << 2) & (position << 3);
+
+        // horizontal and both diagonals
+        for &shift in &[HEIGHT + 1, HEIGHT, HEIGHT + 2] {
+            let mut pair = (position << shift) & (position << (2 * shift));
+            result |= pair & (position << (3 * shift));
+            result |= pair & (position >> shift);
+            pair = (position >> shift) & (position >> (2 * shift));
+            result |= pair & (position << shift);
+            result |= pair & (position >> (3 * shift));
+        }
+
+        result & (BOARD_MASK ^ mask)
+    }
+}
+
+/// Moves sorted by score, returning the best first. Moves with equal scores are returned
+/// in reverse order of adding them.
+struct MoveSorter {
+    entries: [(u64, u32); WIDTH as usize],
+    size: usize,
+}
+
+impl MoveSorter {
+    fn new() -> MoveSorter {
+        MoveSorter { entries: [(0, 0); WIDTH as usize], size: 0 }
+    }
+
+    fn add(&mut self, move_bit: u64, score: u32) {
+        let mut index = self.size;
+        self.size += 1;
+        while index > 0 && self.entries[index - 1].1 > score {
+            self.entries[index] = self.entries[index - 1];
+            index -= 1;
+        }
+        self.entries[index] = (move_bit, score);
+    }
+
+    fn next(&mut self) -> Option<u64> {
+        if self.size == 0 {
+            None
+        } else {
+            self.size -= 1;
+            Some(self.entries[self.size].0)
+        }
+    }
+}
+
+/// Exact solver for the standard 7x6 board.
+///
+/// It uses negamax with alpha-beta pruning and null window searches, a transposition table
+/// storing upper and lower bounds, and only considers moves that don't lose right away.
+/// Scores follow the number of pieces the winner has left when winning: a win with the
+/// last piece of the side to move scores 1, an earlier win scores more, a draw 0.
+///
+/// The transposition table takes up about 84 MB, which is only allocated once the first
+/// position gets solved.
+pub struct Solver {
+    // both empty until the first position gets solved
+    keys: Vec<u32>,
+    values: Vec<u8>,
+    nodes: u64,
+    // when to give up, and whether that happened
+    time_limit: Option<(Timer, u64)>,
+    out_of_time: bool,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver {
+            keys: vec!(),
+            values: vec!(),
+            nodes: 0,
+            time_limit: None,
+            out_of_time: false,
+        }
+    }
+
+    /// gives up solving once the given time from now on has passed, or never for None
+    pub fn set_time_limit(&mut self, millis: Option<u64>) {
+        self.time_limit = millis.map(|millis| (Timer::new(), millis));
+        self.out_of_time = false;
+    }
+
+    /// the number of positions visited so far
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// solves the position on the given board
+    ///
+    /// Returns None for boards other than the standard one, and once the time limit is up.
+    pub fn solve(&mut self, board: &Board) -> Option<Outcome> {
+        if !board.config.is_standard() || self.out_of_time {
+            return None;
+        }
+        match board.status {
+            GameStatus::Won(_) => Some(Outcome::Loss(0)),
+            GameStatus::Draw => Some(Outcome::Draw),
+            GameStatus::Ongoing => {
+                if self.keys.is_empty() {
+                    self.keys = vec!(0; TABLE_SIZE);
+                    self.values = vec!(0; TABLE_SIZE);
+                }
+                let position = Position::from(board);
+                let score = self.solve_position(&position);
+                Some(Solver::outcome(score, position.moves)).filter(|_| !self.out_of_time)
+            }
+        }
+    }
+
+    /// solves the positions after each of the legal moves, in the order of the board's move list
+    ///
+    /// There are no moves once the game is over. Returns None like `solve`.
+    pub fn analyze(&mut self, board: &Board) -> Option<Vec<(Column, Outcome)>> {
+        if !board.config.is_standard() {
+            return None;
+        }
+        if board.status != GameStatus::Ongoing {
+            return Some(vec!());
+        }
+        board.moves.moves().iter().map(|&column| {
+            if board.is_winning_move(column, board.color_to_move) {
+                Some((column, Outcome::Win(1)))
+            } else {
+                let mut sim = *board;
+                sim.play_move(column);
+                self.solve(&sim).map(|outcome| (column, outcome.before_move()))
+            }
+        }).collect()
+    }
+
+    /// returns the best move with its outcome, preferring central columns if moves are equally good
+    ///
+    /// Returns None if the game is over, or like `solve`.
+    pub fn best_move(&mut self, board: &Board) -> Option<(Column, Outcome)> {
+        let mut best: Option<(Column, Outcome)> = None;
+        for (column, outcome) in self.analyze(board)? {
+            match best {
+                Some((_, best_outcome)) if outcome <= best_outcome => (),
+                _ => best = Some((column, outcome))
+            }
+        }
+        best
+    }
+
+    /// converts a score into the number of plies until the end of the game
+    fn outcome(score: i32, moves: i32) -> Outcome {
+        if score == 0 {
+            return Outcome::Draw;
+        }
+        // the winner needs the given score of pieces to be left over, and the winning piece
+        // is played by the side to move for wins and by the opponent for losses
+        let winning_piece = SIZE + 1 - 2 * score.abs();
+        let winner_parity = (if score > 0 { moves + 1 } else { moves }) % 2;
+        let winning_piece = if winning_piece % 2 == winner_parity { winning_piece } else { winning_piece + 1 };
+        let plies = (winning_piece - moves) as u8;
+        if score > 0 { Outcome::Win(plies) } else { Outcome::Loss(plies) }
+    }
+
+    fn solve_position(&mut self, position: &Position) -> i32 {
+        if position.can_win_next() {
+            return (SIZE + 1 - position.moves) / 2;
+        }
+
+        // narrow down the score with null window searches
+        let mut min = -(SIZE - position.moves) / 2;
+        let mut max = (SIZE + 1 - position.moves) / 2;
+        while min < max {
+            let mut med = min + (max - min) / 2;
+            // look at the results around 0 first, which helps finding draws, wins and losses
+            if med <= 0 && min / 2 < med {
+                med = min / 2;
+            } else if med >= 0 && max / 2 > med {
+                med = max / 2;
+            }
+            let score = self.negamax(position, med, med + 1);
+            if self.out_of_time {
+                break;
+            }
+            if score <= med {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+        min
+    }
+
+    /// the side to move must not be able to win with its next move
+    fn negamax(&mut self, position: &Position, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CLOCK_INTERVAL) {
+            if let Some((ref timer, millis)) = self.time_limit {
+                self.out_of_time |= timer.elapsed_millis() >= millis;
+            }
+        }
+        // the result doesn't matter any more, it gets discarded
+        if self.out_of_time {
+            return 0;
+        }
+
+        let next = position.possible_non_losing_moves();
+        if next == 0 {
+            return -(SIZE - position.moves) / 2;
+        }
+        if position.moves >= SIZE - 2 {
+            return 0;
+        }
+
+        let min = -(SIZE - 2 - position.moves) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+        let max = (SIZE - 1 - position.moves) / 2;
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        let key = position.key();
+        match self.get(key) {
+            0 => (),
+            value if value > MAX_SCORE - MIN_SCORE + 1 => { // lower bound
+                let min = value + 2 * MIN_SCORE - MAX_SCORE - 2;
+                if alpha < min {
+                    alpha = min;
+                    if alpha >= beta {
+                        return alpha;
+                    }
+                }
+            },
+            value => { // upper bound
+                let max = value + MIN_SCORE - 1;
+                if beta > max {
+                    beta = max;
+                    if alpha >= beta {
+                        return beta;
+                    }
+                }
+            }
+        }
+
+        let mut moves = MoveSorter::new();
+        for &column in COLUMN_ORDER.iter().rev() {
+            let move_bit = next & Position::column_mask(column);
+            if move_bit != 0 {
+                moves.add(move_bit, position.move_score(move_bit));
+            }
+        }
+
+        while let Some(move_bit) = moves.next() {
+            let mut child = *position;
+            child.play(move_bit);
+            let score = -self.negamax(&child, -beta, -alpha);
+            if self.out_of_time {
+                return 0;
+            }
+            if score >= beta {
+                self.put(key, score + MAX_SCORE - 2 * MIN_SCORE + 2);
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        self.put(key, alpha - MIN_SCORE + 1);
+        alpha
+    }
+
+    fn get(&self, key: u64) -> i32 {
+        let index = (key % TABLE_SIZE as u64) as usize;
+        if self.keys[index] == key as u32 {
+            self.values[index] as i32
+        } else {
+            0
+        }
+    }
+
+    fn put(&mut self, key: u64, value: i32) {
+        let index = (key % TABLE_SIZE as u64) as usize;
+        self.keys[index] = key as u32;
+        self.values[index] = value as u8;
+    }
+}
+
+#[cfg(test)]
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+/// plain minimax over all moves, for comparison
+#[cfg(test)]
+fn brute_force(board: &Board) -> Outcome {
+    match board.status {
+        GameStatus::Won(_) => Outcome::Loss(0),
+        GameStatus::Draw => Outcome::Draw,
+        GameStatus::Ongoing => board.moves.moves().iter().map(|&column| {
+            let mut sim = *board;
+            sim.play_move(column);
+            brute_force(&sim).before_move()
+        }).max().unwrap()
+    }
+}
+
+#[test]
+fn test_outcome_order() {
+    assert!(Outcome::Win(1) > Outcome::Win(3));
+    assert!(Outcome::Win(41) > Outcome::Draw);
+    assert!(Outcome::Draw > Outcome::Loss(40));
+    assert!(Outcome::Loss(4) > Outcome::Loss(2));
+}
+
+#[test]
+fn test_solve_immediate_win() {
+    let mut solver = Solver::new();
+    let board = Board::from_moves_str("445566").unwrap();
+    assert_eq!(solver.solve(&board), Some(Outcome::Win(1)));
+    assert_eq!(solver.best_move(&board), Some((2, Outcome::Win(1))));
+
+    let board = Board::from_moves_str("4455661").unwrap();
+    assert_eq!(solver.solve(&board), Some(Outcome::Loss(2)));
+
+    let board = Board::from_moves_str("4455667").unwrap();
+    assert_eq!(solver.solve(&board), Some(Outcome::Loss(0)));
+    assert_eq!(solver.best_move(&board), None);
+}
+
+#[test]
+fn test_solve_against_brute_force() {
+    let mut solver = Solver::new();
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut checked = 0;
+    while checked < 20 {
+        let mut board = Board::new();
+        while board.status == GameStatus::Ongoing && board.played_moves().len() < 34 {
+            let column = *rng.choose(&board.moves.moves()).unwrap();
+            board.play_move(column);
+        }
+        if board.status != GameStatus::Ongoing {
+            continue;
+        }
+        assert_eq!(solver.solve(&board), Some(brute_force(&board)), "position {}", board.to_moves_str());
+        checked += 1;
+    }
+}
+
+#[test]
+fn test_unsupported_board() {
+    use row4::config::BoardConfig;
+
+    let mut solver = Solver::new();
+    let board = Board::with_config(BoardConfig::new(5, 4, 3).unwrap());
+    assert_eq!(solver.solve(&board), None);
+    assert_eq!(solver.best_move(&board), None);
+    // nothing gets allocated for it
+    assert!(solver.keys.is_empty());
+}
+
+#[test]
+fn test_time_limit() {
+    let mut solver = Solver::new();
+    solver.set_time_limit(Some(0));
+    assert_eq!(solver.solve(&Board::new()), None);
+    assert_eq!(solver.best_move(&Board::from_moves_str("4453").unwrap()), None);
+
+    // what was left over when time ran out doesn't spoil later results
+    solver.set_time_limit(None);
+    let board = Board::from_moves_str("4455661").unwrap();
+    assert_eq!(solver.solve(&board), Some(Outcome::Loss(2)));
+}
+
+/// solves the empty board, run with `cargo test --release -- --ignored --nocapture solve_empty_board`
+#[test]
+#[ignore]
+fn solve_empty_board() {
+    use row4::time::Timer;
+
+    let mut solver = Solver::new();
+    let timer = Timer::new();
+    let outcome = solver.solve(&Board::new());
+    println!("empty board: {:?} in {} ms, {} positions", outcome, timer.elapsed_millis(), solver.nodes());
+    assert_eq!(outcome, Some(Outcome::Win(41)));
+}
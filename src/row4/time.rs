@@ -5,6 +5,12 @@ pub struct Timer {
     start: SystemTime
 }
 
+impl Default for Timer {
+    fn default() -> Timer {
+        Timer::new()
+    }
+}
+
 impl Timer {
     pub fn new() -> Timer {
         Timer {
@@ -0,0 +1,213 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use row4::*;
+use row4::board::Board;
+use row4::score::Score;
+
+// each entry takes two 64 bit words
+const BYTES_PER_ENTRY: usize = 16;
+
+/// mixed into the key when searching for blue, as scores are from the view of the searching side
+pub const BLUE_KEY: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// How the score of an entry relates to the real value of the position.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Bound {
+    Exact,
+    // the real value is at least the score
+    Lower,
+    // the real value is at most the score
+    Upper,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TableEntry {
+    pub score: Score,
+    // the remaining depth the position was searched with
+    pub depth: u8,
+    pub bound: Bound,
+    pub best_move: Option<Column>,
+}
+
+impl TableEntry {
+    // score in the lowest 32 bits, then depth, bound, best move + 1 and generation
+    fn pack(&self, generation: u8) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2
+        };
+        let best_move = self.best_move.map_or(0, |column| column as u64 + 1);
+        self.score.raw() as u32 as u64 | (self.depth as u64) << 32 | bound << 40 | best_move << 42 | (generation as u64) << 48
+    }
+
+    fn unpack(data: u64) -> TableEntry {
+        let bound = match (data >> 40) & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper
+        };
+        let best_move = match (data >> 42) & 0b1111 {
+            0 => None,
+            column => Some(column as Column - 1)
+        };
+        TableEntry { score: Score::from_raw(data as u32 as i32), depth: (data >> 32) as u8, bound, best_move }
+    }
+}
+
+fn generation_of(data: u64) -> u8 {
+    (data >> 48) as u8
+}
+
+/// Results of searched positions, with a fixed size that is shared between search threads.
+///
+/// Entries are stored without locks as a pair of the data and the data xor'ed with the key,
+/// so that a pair torn apart by concurrent writes doesn't match its key any more. Positions
+/// are grouped in buckets of two entries: the first one keeps the deepest search of the
+/// current generation, the second one always takes the newest result. Best moves are kept
+/// in the orientation of the canonical key, so mirrored positions share their entries.
+pub struct TranspositionTable {
+    words: Vec<AtomicU64>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// creates a table that takes up about the given number of megabytes, with room for two entries at least
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let num_buckets = usize::max(1, megabytes * 1024 * 1024 / (2 * BYTES_PER_ENTRY));
+        TranspositionTable {
+            words: (0..num_buckets * 4).map(|_| AtomicU64::new(0)).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// the number of entries that fit into the table
+    pub fn capacity(&self) -> usize {
+        self.words.len() / 2
+    }
+
+    /// starts a new generation, after which entries of earlier searches get replaced first
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for word in &self.words {
+            word.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn key(board: &Board, own_color: Color) -> u64 {
+        match own_color {
+            Color::Red => board.canonical_key(),
+            Color::Blue => board.canonical_key() ^ BLUE_KEY
+        }
+    }
+
+    // the index of the first word of the bucket
+    fn bucket(&self, key: u64) -> usize {
+        (key % (self.words.len() / 4) as u64) as usize * 4
+    }
+
+    // returns the data of the entry at the given word index, if it belongs to the key
+    fn read(&self, index: usize, key: u64) -> Option<u64> {
+        let check = self.words[index].load(Ordering::Relaxed);
+        let data = self.words[index + 1].load(Ordering::Relaxed);
+        if check ^ data == key && data != 0 {
+            Some(data)
+        } else {
+            None
+        }
+    }
+
+    fn write(&self, index: usize, key: u64, data: u64) {
+        self.words[index].store(key ^ data, Ordering::Relaxed);
+        self.words[index + 1].store(data, Ordering::Relaxed);
+    }
+
+    /// looks up the result for the position, searched from the view of the given color
+    pub fn get(&self, board: &Board, own_color: Color) -> Option<TableEntry> {
+        let key = TranspositionTable::key(board, own_color);
+        let bucket = self.bucket(key);
+        self.read(bucket, key).or_else(|| self.read(bucket + 2, key)).map(|data| {
+            let entry = TableEntry::unpack(data);
+            TableEntry { best_move: entry.best_move.map(|column| board.canonical_column(column)), ..entry }
+        })
+    }
+
+    /// stores the result for the position, searched from the view of the given color
+    pub fn store(&self, board: &Board, own_color: Color, entry: TableEntry) {
+        let key = TranspositionTable::key(board, own_color);
+        let bucket = self.bucket(key);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let canonical = TableEntry { best_move: entry.best_move.map(|column| board.canonical_column(column)), ..entry };
+        let data = canonical.pack(generation);
+
+        let deepest = self.words[bucket + 1].load(Ordering::Relaxed);
+        let replace_deepest = self.read(bucket, key).is_some()
+            || deepest == 0
+            || generation_of(deepest) != generation
+            || TableEntry::unpack(deepest).depth <= entry.depth;
+        if replace_deepest {
+            self.write(bucket, key, data);
+        } else {
+            self.write(bucket + 2, key, data);
+        }
+    }
+}
+
+#[test]
+fn test_pack() {
+    let entries = [
+        TableEntry { score: Score::loss_at(17), depth: 9, bound: Bound::Upper, best_move: Some(6) },
+        TableEntry { score: Score::estimate(0.25), depth: 0, bound: Bound::Exact, best_move: None },
+        TableEntry { score: Score::win_at(42), depth: 255, bound: Bound::Lower, best_move: Some(0) },
+    ];
+    for entry in &entries {
+        assert_eq!(TableEntry::unpack(entry.pack(200)), *entry);
+        assert_eq!(generation_of(entry.pack(200)), 200);
+    }
+}
+
+#[test]
+fn test_store_and_get() {
+    let table = TranspositionTable::new(1);
+    assert_eq!(table.capacity(), 1024 * 1024 / BYTES_PER_ENTRY);
+
+    let board = Board::from_moves_str("112").unwrap();
+    let mirrored = Board::from_moves_str("776").unwrap();
+    let entry = TableEntry { score: Score::estimate(0.75), depth: 3, bound: Bound::Exact, best_move: Some(1) };
+    table.store(&board, Color::Blue, entry);
+
+    assert_eq!(table.get(&board, Color::Blue), Some(entry));
+    assert_eq!(table.get(&mirrored, Color::Blue), Some(TableEntry { best_move: Some(5), ..entry }));
+    assert_eq!(table.get(&board, Color::Red), None);
+    assert_eq!(table.get(&Board::new(), Color::Blue), None);
+
+    table.clear();
+    assert_eq!(table.get(&board, Color::Blue), None);
+}
+
+#[test]
+fn test_replacement() {
+    // a single bucket, so that all positions compete for it
+    let table = TranspositionTable { words: (0..4).map(|_| AtomicU64::new(0)).collect(), generation: AtomicU8::new(0) };
+    let boards: Vec<Board> = ["1", "2", "3", "4"].iter().map(|moves| Board::from_moves_str(moves).unwrap()).collect();
+    let entry = |depth| TableEntry { score: Score::DRAW, depth, bound: Bound::Exact, best_move: None };
+
+    table.store(&boards[0], Color::Red, entry(5));
+    table.store(&boards[1], Color::Red, entry(2));
+    // the deep entry stays, the shallow one is always replaced
+    assert!(table.get(&boards[0], Color::Red).is_some());
+    assert!(table.get(&boards[1], Color::Red).is_some());
+    table.store(&boards[2], Color::Red, entry(3));
+    assert!(table.get(&boards[0], Color::Red).is_some());
+    assert!(table.get(&boards[1], Color::Red).is_none());
+    assert!(table.get(&boards[2], Color::Red).is_some());
+
+    // entries of earlier searches make room
+    table.new_search();
+    table.store(&boards[3], Color::Red, entry(1));
+    assert!(table.get(&boards[0], Color::Red).is_none());
+    assert!(table.get(&boards[3], Color::Red).is_some());
+}